@@ -0,0 +1,145 @@
+use crate::{error::check_response, state::State, upload::file_parts};
+use anyhow::Result;
+use common::{AppendReceipt, BatchId, ErrorBody};
+use merkle_tree::{
+    hashing::TreeHasher, leaf::FileLeaf, merkletree::MerkleTree, proof::digests_match,
+};
+use reqwest::{multipart, Client, StatusCode};
+use std::{net::SocketAddr, path::PathBuf};
+use tracing::info;
+
+/// Appends the files at `paths` to a batch uploaded from this client,
+/// and records the new root once the server proved that it extends the old one.
+pub(crate) async fn append_files(
+    client: Client,
+    state: &mut State,
+    addr: SocketAddr,
+    batch_id: &BatchId,
+    paths: &[PathBuf],
+) -> Result<()> {
+    let old_root = state
+        .batch_root_map
+        .get(batch_id)
+        .ok_or(anyhow::Error::msg("Batch not found in the state"))?
+        .clone();
+    if state.batch_key_map.contains_key(batch_id) {
+        return Err(anyhow::Error::msg(
+            "Keyed batches cannot be appended to, upload the batch again with --replace",
+        ));
+    }
+    let old_size = *state
+        .batch_size_map
+        .get(batch_id)
+        .ok_or(anyhow::Error::msg(
+            "The number of files of the batch is unknown, upload it again",
+        ))?;
+
+    let (parts, leaves) = file_parts(paths, None).await?;
+    let mut form = multipart::Form::new().part(
+        common::MANIFEST_FIELD,
+        multipart::Part::text(serde_json::to_string(&leaves)?).mime_str("application/json")?,
+    );
+    for (part_name, part) in parts {
+        form = form.part(part_name, part);
+    }
+
+    let route = common::APPEND_ROUTE.replace(":batch_id", batch_id);
+    let response = client
+        .post(format!("http://{}{}?old_size={}", addr, route, old_size))
+        .multipart(form)
+        .send()
+        .await?;
+    if response.status() == StatusCode::CONFLICT {
+        let ErrorBody { message, .. } = response.json().await?;
+        return Err(anyhow::Error::msg(format!(
+            "{}. Rename the files, or upload the batch again if it changed elsewhere",
+            message
+        )));
+    }
+    let response = check_response(response).await?;
+    info!("Files were appended");
+    let receipt: AppendReceipt = response.json().await?;
+    check_append_receipt(&receipt, batch_id, old_size, &old_root, &leaves)?;
+    info!("Server proved that the new root extends the old one");
+    for (index, leaf) in leaves.iter().enumerate() {
+        info!("File {} is {}", old_size as usize + index, leaf.path);
+    }
+
+    let AppendReceipt { receipt, .. } = receipt;
+    state
+        .batch_root_map
+        .insert(batch_id.clone(), receipt.root_hash);
+    state.batch_size_map.insert(batch_id.clone(), receipt.count);
+    state.batch_head_map.remove(batch_id);
+
+    state.save_state()?;
+    info!("State was updated");
+
+    Ok(())
+}
+
+/// Checks that the receipt ends with `leaves`, that its root is the root of its leaves,
+/// and that its proof shows that this root extends `old_root`, of `old_size` files.
+pub(crate) fn check_append_receipt(
+    AppendReceipt { receipt, proof }: &AppendReceipt,
+    batch_id: &BatchId,
+    old_size: u64,
+    old_root: &[u8],
+    leaves: &[FileLeaf],
+) -> Result<()> {
+    if receipt.batch_id != *batch_id
+        || !std::ptr::eq(receipt.algorithm, common::DIGEST)
+        || receipt.count != receipt.leaves.len() as u64
+        || receipt.count != old_size + leaves.len() as u64
+    {
+        return Err(anyhow::Error::msg(
+            "The server recorded another batch, the append was not recorded",
+        ));
+    }
+
+    let appended = &receipt.leaves[old_size as usize..];
+    let differing: Vec<_> = leaves
+        .iter()
+        .zip(appended)
+        .enumerate()
+        .filter(|(_, (ours, theirs))| {
+            ours.path != theirs.path
+                || !digests_match(&common::DIGEST.leaf_hash(*ours), &theirs.leaf_hash)
+        })
+        .map(|(index, (ours, _))| format!("{} ({})", ours.path, old_size as usize + index))
+        .collect();
+    if !differing.is_empty() {
+        return Err(anyhow::Error::msg(format!(
+            "The server built another tree, the append was not recorded. Files that differ: {}",
+            differing.join(", ")
+        )));
+    }
+
+    let tree = MerkleTree::from_hashed_leaves_with_profile(
+        common::DIGEST,
+        common::PROFILE,
+        receipt
+            .leaves
+            .iter()
+            .map(|leaf| (&leaf.leaf_hash, ()))
+            .collect(),
+    );
+    if !digests_match(tree.root_hash(), &receipt.root_hash) {
+        return Err(anyhow::Error::msg(
+            "The root of the receipt is not the root of its leaves, the append was not recorded",
+        ));
+    }
+
+    if !std::ptr::eq(proof.algorithm, common::DIGEST)
+        || proof.profile != common::PROFILE
+        || proof.old_size as u64 != old_size
+        || proof.new_size as u64 != receipt.count
+        || !proof.validate(old_root, &receipt.root_hash)
+    {
+        return Err(anyhow::Error::msg(
+            "The server did not prove that the new root extends the old one, \
+             the append was not recorded",
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use crate::{error::check_response, state::State};
+use anyhow::Result;
+use merkle_tree::{
+    consistency::ConsistencyProof,
+    log::{LogEntry, LogHead},
+    proof::{digests_match, Proof},
+};
+use reqwest::Client;
+use std::net::SocketAddr;
+use tracing::info;
+
+/// Checks that the log of the server only grew since the last audit,
+/// and that it holds the root of `batch_id` known to the state.
+pub(crate) async fn audit_log(
+    client: Client,
+    state: &mut State,
+    addr: SocketAddr,
+    batch_id: &common::BatchId,
+) -> Result<()> {
+    let root_hash = state
+        .batch_root_map
+        .get(batch_id)
+        .ok_or(anyhow::Error::msg("no such batch_id in state"))?;
+
+    let response = client
+        .get(format!("http://{}{}", addr, common::LOG_HEAD_ROUTE))
+        .send()
+        .await?;
+    let head: LogHead = check_response(response).await?.json().await?;
+    if !std::ptr::eq(head.algorithm, common::DIGEST) || head.profile != common::PROFILE {
+        return Err(anyhow::Error::msg("The log uses another hashing scheme"));
+    }
+    info!("Log has {} entries", head.size);
+
+    if let Some(old_head) = &state.log_head {
+        let response = client
+            .get(format!(
+                "http://{}{}?old_size={}&new_size={}",
+                addr,
+                common::LOG_CONSISTENCY_ROUTE,
+                old_head.size,
+                head.size
+            ))
+            .send()
+            .await?;
+        let proof: ConsistencyProof = check_response(response).await?.json().await?;
+        if !old_head.is_consistent_with(&head, &proof) {
+            return Err(anyhow::Error::msg(
+                "The log is not consistent with the last audited head",
+            ));
+        }
+        info!("Log is consistent with the last audited head");
+    }
+
+    let response = client
+        .get(format!(
+            "http://{}{}?batch_id={}&size={}",
+            addr,
+            common::LOG_INCLUSION_ROUTE,
+            batch_id,
+            head.size
+        ))
+        .send()
+        .await?;
+    let proof: Proof<LogEntry> = check_response(response).await?.json().await?;
+    if !head.is_for_proof(&proof) || !proof.validate(&head.root_hash) || !proof.is_for_value() {
+        return Err(anyhow::Error::msg("The inclusion proof is not valid"));
+    }
+
+    let entry = &proof.value;
+    let size_matches = state
+        .batch_size_map
+        .get(batch_id)
+        .map_or(true, |size| *size == entry.size);
+    if entry.batch_id != batch_id.as_str()
+        || !digests_match(&entry.root_hash, root_hash)
+        || !size_matches
+    {
+        return Err(anyhow::Error::msg(
+            "The log holds another root for this batch",
+        ));
+    }
+    info!(
+        "Root of batch {} is entry {} of the log",
+        batch_id,
+        proof.index(head.size as usize)
+    );
+
+    state.log_head = Some(head);
+    state.save_state()?;
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use clap::Subcommand;
+use clap::{Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Subcommand)]
@@ -8,13 +8,104 @@ pub(crate) enum Commands {
         batch_id: common::BatchId,
         #[arg(short, long)]
         paths: Vec<PathBuf>,
+        /// Hash the batch with a secret key kept in the state, so that
+        /// proofs cannot be used to confirm guesses of the file contents
+        #[arg(long)]
+        keyed: bool,
+        /// Replace the batch if the server holds other files under its id
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Append files to a batch uploaded from this client, once the server
+    /// proves that the new root extends the one in the state
+    AppendFiles {
+        #[arg(short, long)]
+        batch_id: common::BatchId,
+        #[arg(short, long)]
+        paths: Vec<PathBuf>,
     },
     DownloadFile {
         #[arg(short, long)]
         batch_id: common::BatchId,
         #[arg(short, long)]
         file_index: u64,
+        /// Directory to restore the file to, under its original name
         #[arg(short, long)]
-        destination_path: PathBuf,
+        destination_dir: PathBuf,
+        /// Also save the proof of the file to this path
+        #[arg(long)]
+        proof_path: Option<PathBuf>,
+        /// Also restore the setuid, setgid and sticky bits of the file
+        #[arg(long)]
+        keep_special_bits: bool,
     },
+    /// Delete a batch from the server, and forget it; needs `DELETE_TOKEN`
+    DeleteBatch {
+        #[arg(short, long)]
+        batch_id: common::BatchId,
+    },
+    /// Delete a file of a batch from the server, which keeps the root
+    /// of the batch; needs `DELETE_TOKEN`
+    DeleteFile {
+        #[arg(short, long)]
+        batch_id: common::BatchId,
+        #[arg(short, long)]
+        file_index: u64,
+    },
+    /// Print a proof saved with `download-file --proof-path`
+    InspectProof {
+        #[arg(short, long)]
+        path: PathBuf,
+        #[arg(short, long, value_enum, default_value_t = ProofFormat::Ascii)]
+        format: ProofFormat,
+        /// Also check the proof against the root of this batch
+        #[arg(short, long)]
+        batch_id: Option<common::BatchId>,
+    },
+    /// Generate an Ed25519 key pair to sign tree heads with
+    GenerateSigningKey {
+        /// Where to write the PKCS#8 key pair; the file must not exist
+        #[arg(short, long)]
+        key_path: PathBuf,
+    },
+    /// Sign the head of a batch uploaded from this client, and keep it in the state
+    SignHead {
+        #[arg(short, long)]
+        batch_id: common::BatchId,
+        #[arg(short, long)]
+        key_path: PathBuf,
+    },
+    /// Write the signed head of a batch to a file, to share it
+    ExportHead {
+        #[arg(short, long)]
+        batch_id: common::BatchId,
+        #[arg(short, long)]
+        path: PathBuf,
+    },
+    /// Verify a signed head, and check it against the state if the batch is known
+    VerifyHead {
+        #[arg(short, long)]
+        path: PathBuf,
+        /// Hex encoded Ed25519 public key the head must be signed with
+        #[arg(long)]
+        public_key: Option<String>,
+        /// Trust the root of the head for downloads, if the batch is not in the state yet;
+        /// needs `--public-key`
+        #[arg(long)]
+        import: bool,
+    },
+    /// Check that the log of the server holds the root of a batch,
+    /// and only grew since the last audit
+    AuditLog {
+        #[arg(short, long)]
+        batch_id: common::BatchId,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum ProofFormat {
+    /// Indented tree with truncated hashes
+    Ascii,
+    /// Graphviz DOT graph
+    Dot,
 }
@@ -0,0 +1,93 @@
+use merkle_tree::{leaf::FileLeaf, proof::digests_match};
+use ring::{digest, hmac};
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Hashes the content of a file into the `content_hash` of its leaf:
+/// a plain digest, or an HMAC tag for keyed batches.
+pub(crate) enum ContentHasher {
+    Plain(digest::Context),
+    Keyed(hmac::Context),
+}
+
+impl ContentHasher {
+    pub(crate) fn new(key: Option<&hmac::Key>) -> Self {
+        match key {
+            Some(key) => ContentHasher::Keyed(hmac::Context::with_key(key)),
+            None => ContentHasher::Plain(digest::Context::new(common::DIGEST)),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            ContentHasher::Plain(context) => context.update(data),
+            ContentHasher::Keyed(context) => context.update(data),
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        match self {
+            ContentHasher::Plain(context) => context.finish().as_ref().to_vec(),
+            ContentHasher::Keyed(context) => context.sign().as_ref().to_vec(),
+        }
+    }
+}
+
+/// Reads a file as it is uploaded, and fails at its end if its content no longer
+/// matches its leaf, which the server would only report as a mismatch with the manifest.
+pub(crate) struct CheckedReader<R> {
+    reader: R,
+    hasher: Option<ContentHasher>,
+    size: u64,
+    leaf: FileLeaf,
+}
+
+impl<R> CheckedReader<R> {
+    pub(crate) fn new(reader: R, leaf: FileLeaf, key: Option<&hmac::Key>) -> Self {
+        Self {
+            reader,
+            hasher: Some(ContentHasher::new(key)),
+            size: 0,
+            leaf,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CheckedReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.reader).poll_read(cx, buf))?;
+        let read = &buf.filled()[filled..];
+        if !read.is_empty() || buf.remaining() == 0 {
+            if let Some(hasher) = &mut this.hasher {
+                hasher.update(read);
+            }
+            this.size += read.len() as u64;
+            return Poll::Ready(Ok(()));
+        }
+
+        if let Some(hasher) = this.hasher.take() {
+            if this.size != this.leaf.size
+                || !digests_match(&hasher.finish(), &this.leaf.content_hash)
+            {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "{} changed while it was uploaded, upload it again",
+                        this.leaf.path
+                    ),
+                )));
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{error::check_response, state::State};
+use anyhow::Result;
+use common::BatchId;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use std::net::SocketAddr;
+use tracing::info;
+
+/// Returns a delete request for `route`, bearing the delete token of the server.
+fn delete_request(client: &Client, addr: SocketAddr, route: &str) -> Result<RequestBuilder> {
+    let token = std::env::var(common::DELETE_TOKEN).map_err(|_| {
+        anyhow::Error::msg(format!(
+            "Set {} to the delete token of the server",
+            common::DELETE_TOKEN
+        ))
+    })?;
+    Ok(client
+        .delete(format!("http://{}{}", addr, route))
+        .bearer_auth(token))
+}
+
+/// Deletes a batch from the server, and forgets it. A batch the server
+/// does not have is forgotten as well.
+pub(crate) async fn delete_batch(
+    client: Client,
+    state: &mut State,
+    addr: SocketAddr,
+    batch_id: &BatchId,
+) -> Result<()> {
+    let route = common::BATCH_ROUTE.replace(":batch_id", batch_id);
+    let response = delete_request(&client, addr, &route)?.send().await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        info!("Server did not have the batch");
+    } else {
+        check_response(response).await?;
+        info!("Batch was deleted");
+    }
+
+    state.batch_root_map.remove(batch_id);
+    state.batch_key_map.remove(batch_id);
+    state.batch_size_map.remove(batch_id);
+    state.batch_head_map.remove(batch_id);
+
+    state.save_state()?;
+    info!("State was updated");
+
+    Ok(())
+}
+
+/// Deletes a file of a batch from the server. The batch keeps its root,
+/// so the state is unchanged and the other files can still be verified.
+pub(crate) async fn delete_file(
+    client: Client,
+    state: &State,
+    addr: SocketAddr,
+    batch_id: &BatchId,
+    file_index: u64,
+) -> Result<()> {
+    let size = state.batch_size_map.get(batch_id);
+    if let Some(size) = size.filter(|size| file_index >= **size) {
+        return Err(anyhow::Error::msg(format!(
+            "Batch has {} files, and no file {}",
+            size, file_index
+        )));
+    }
+
+    let route = common::FILE_ROUTE
+        .replace(":batch_id", batch_id)
+        .replace(":file_index", &file_index.to_string());
+    let response = delete_request(&client, addr, &route)?.send().await?;
+    check_response(response).await?;
+    info!(
+        "File {} was deleted, the root of the batch is unchanged",
+        file_index
+    );
+
+    Ok(())
+}
@@ -1,17 +1,34 @@
-use crate::state::State;
+use crate::{content::ContentHasher, error::check_response, state::State};
 use anyhow::Result;
-use merkle_tree::proof::Proof;
-use reqwest::Client;
-use std::{io::Cursor, net::SocketAddr, path::PathBuf};
+use merkle_tree::{
+    leaf::FileLeaf,
+    proof::{digests_match, Proof},
+};
+use reqwest::{Client, Response};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+use tokio::io::AsyncWriteExt;
 use tracing::info;
 
+/// Where and how a downloaded file is restored.
+pub(crate) struct Destination<'a> {
+    /// The directory to restore the file to, under its original name
+    pub(crate) dir: &'a Path,
+    /// Whether the setuid, setgid and sticky bits of the file are restored
+    pub(crate) keep_special_bits: bool,
+}
+
 pub(crate) async fn download_file(
     client: Client,
     state: &State,
     addr: SocketAddr,
-    file_name: &PathBuf,
+    destination: Destination<'_>,
     file_index: &u64,
     batch_id: &common::BatchId,
+    proof_path: Option<&PathBuf>,
 ) -> Result<()> {
     let response = client
         .get(format!(
@@ -23,26 +40,151 @@ pub(crate) async fn download_file(
         ))
         .send()
         .await?;
+    let mut response = check_response(response).await?;
     let header = response
         .headers()
         .get(common::PROOF_HEADER)
         .ok_or(anyhow::Error::msg("proof header was not provided"))?;
 
-    let proof: Proof<Vec<u8>> = serde_json::from_str(header.to_str()?)?;
+    let proof: Proof<FileLeaf> = serde_json::from_str(header.to_str()?)?;
+    if let Some(proof_path) = proof_path {
+        std::fs::write(proof_path, header.as_bytes())?;
+        info!("Proof was saved");
+    }
+
+    verify_proof(state, batch_id, &proof)?;
+    info!("Proof is valid");
+
+    let leaf = &proof.value;
+    if !FileLeaf::is_safe_path(&leaf.path) {
+        return Err(anyhow::Error::msg(format!(
+            "Refusing to write to unsafe path {}",
+            leaf.path
+        )));
+    }
+    let file_name = destination.dir.join(&leaf.path);
+    if let Some(parent) = file_name.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    // The content goes to a temporary file next to the destination, which only replaces
+    // an existing file once the content is verified.
+    let temp_name = temp_file_name(&file_name)?;
+    let result = receive_content(
+        &mut response,
+        state,
+        batch_id,
+        &temp_name,
+        leaf,
+        destination.keep_special_bits,
+    )
+    .await;
+    let result = match result {
+        Ok(()) => tokio::fs::rename(&temp_name, &file_name)
+            .await
+            .map_err(anyhow::Error::from),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        let _ = tokio::fs::remove_file(&temp_name).await;
+        return Err(err);
+    }
+    info!("File was downloaded to {}", file_name.display());
+    Ok(())
+}
+
+/// Returns a new, random name for a temporary file in the directory of `file_name`.
+fn temp_file_name(file_name: &Path) -> Result<PathBuf> {
+    let mut id = [0; 8];
+    SystemRandom::new()
+        .fill(&mut id)
+        .map_err(|_| anyhow::Error::msg("could not generate a temporary file name"))?;
+    let name = file_name
+        .file_name()
+        .ok_or(anyhow::Error::msg("not a filename"))?
+        .to_string_lossy();
+    Ok(file_name.with_file_name(format!(".{}.{}.part", name, hex::encode(id))))
+}
+
+/// Writes the content of the response to `temp_name`, and checks it against `leaf`.
+async fn receive_content(
+    response: &mut Response,
+    state: &State,
+    batch_id: &common::BatchId,
+    temp_name: &Path,
+    leaf: &FileLeaf,
+    keep_special_bits: bool,
+) -> Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp_name)
+        .await?;
+    let mut context = ContentHasher::new(state.batch_key(batch_id).as_ref());
+    let mut size = 0;
+    while let Some(chunk) = response.chunk().await? {
+        context.update(&chunk);
+        size += chunk.len() as u64;
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    if size != leaf.size || !digests_match(&context.finish(), &leaf.content_hash) {
+        return Err(anyhow::Error::msg(
+            "File content does not match the proven leaf",
+        ));
+    }
+    restore_metadata(temp_name, leaf, keep_special_bits)
+}
 
+/// Checks that `proof` is a valid proof of a file leaf for the given batch,
+/// with the key of the batch if it is keyed.
+pub(crate) fn verify_proof(
+    state: &State,
+    batch_id: &common::BatchId,
+    proof: &Proof<FileLeaf>,
+) -> Result<()> {
     let root_hash = state
         .batch_root_map
         .get(batch_id)
         .ok_or(anyhow::Error::msg("no such batch_id in state"))?;
 
-    if proof.validate(root_hash) {
-        info!("Proof is valid");
-        let mut file = std::fs::File::create(file_name)?;
-        let mut content = Cursor::new(response.bytes().await?);
-        std::io::copy(&mut content, &mut file)?;
-        info!("File was downloaded");
-        Ok(())
-    } else {
-        Err(anyhow::Error::msg("Proof was not valid"))
+    let valid = proof.profile == common::PROFILE
+        && match state.batch_key(batch_id) {
+            Some(key) => {
+                proof.validate_with_key(root_hash, &key) && proof.is_for_value_with_key(&key)
+            }
+            None => proof.validate(root_hash) && proof.is_for_value(),
+        }
+        && state
+            .batch_head_map
+            .get(batch_id)
+            .map_or(true, |signed| signed.head.is_for_proof(proof));
+    if !valid {
+        return Err(anyhow::Error::msg("Proof was not valid"));
+    }
+    Ok(())
+}
+
+/// Restores the permissions and modification time recorded in `leaf`, if any.
+/// The setuid, setgid and sticky bits are only restored with `keep_special_bits`.
+fn restore_metadata(file_name: &Path, leaf: &FileLeaf, keep_special_bits: bool) -> Result<()> {
+    #[cfg(unix)]
+    if let Some(mode) = leaf.mode {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = if keep_special_bits {
+            mode
+        } else {
+            mode & 0o777
+        };
+        std::fs::set_permissions(file_name, std::fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = keep_special_bits;
+    if let Some(mtime) = leaf.mtime {
+        let mtime = filetime::FileTime::from_unix_time(mtime as i64, 0);
+        filetime::set_file_mtime(file_name, mtime)?;
     }
+    Ok(())
 }
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use common::{ErrorBody, ErrorKind};
+use reqwest::Response;
+
+/// Returns `response` if it is successful, or an error that explains
+/// the error body of the server and what to do about it.
+pub(crate) async fn check_response(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let text = response.text().await.unwrap_or_default();
+    let message = match serde_json::from_str::<ErrorBody>(&text) {
+        Ok(ErrorBody { kind, message }) => match kind {
+            ErrorKind::NotFound => format!(
+                "Not found: {}. Check the batch id and the file index",
+                message
+            ),
+            ErrorKind::BadRequest => format!("The server refused the request: {}", message),
+            ErrorKind::Conflict => format!(
+                "{}. Choose another batch id, or pass --replace to replace it; \
+                 a batch that files were appended to is only uploaded again with --replace",
+                message
+            ),
+            ErrorKind::Unauthorized => format!(
+                "{}. Set {} to the delete token of the server",
+                message,
+                common::DELETE_TOKEN
+            ),
+            ErrorKind::TooLarge => format!(
+                "{}. Upload fewer or smaller files, or raise MAX_UPLOAD_SIZE on the server",
+                message
+            ),
+            ErrorKind::Internal => format!(
+                "The server failed: {}. Try again later, or check its logs",
+                message
+            ),
+        },
+        Err(_) => format!("The server answered {}: {}", status, text),
+    };
+    Err(anyhow::Error::msg(message))
+}
@@ -0,0 +1,154 @@
+use crate::state::State;
+use anyhow::Result;
+use merkle_tree::{
+    head::{SignedTreeHead, TreeHead},
+    proof::digests_match,
+};
+use ring::{
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair},
+};
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+pub(crate) fn generate_signing_key(key_path: &PathBuf) -> Result<()> {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map_err(|_| anyhow::Error::msg("could not generate a key pair"))?;
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+        .map_err(|err| anyhow::Error::msg(err.to_string()))?;
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(key_path)?.write_all(pkcs8.as_ref())?;
+    info!("Key pair was saved");
+
+    println!("{}", hex::encode(key_pair.public_key()));
+    Ok(())
+}
+
+pub(crate) fn sign_head(
+    state: &mut State,
+    batch_id: &common::BatchId,
+    key_path: &PathBuf,
+) -> Result<()> {
+    let key_pair = Ed25519KeyPair::from_pkcs8(&std::fs::read(key_path)?)
+        .map_err(|err| anyhow::Error::msg(err.to_string()))?;
+
+    let root_hash = state
+        .batch_root_map
+        .get(batch_id)
+        .ok_or(anyhow::Error::msg("no such batch_id in state"))?;
+    let size = *state
+        .batch_size_map
+        .get(batch_id)
+        .ok_or(anyhow::Error::msg(
+            "the size of the batch is unknown, upload it again",
+        ))?;
+
+    let head = TreeHead {
+        batch_id: batch_id.to_string(),
+        algorithm: common::DIGEST,
+        size,
+        root_hash: root_hash.clone(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    };
+    state
+        .batch_head_map
+        .insert(batch_id.clone(), head.sign(&key_pair));
+    state.save_state()?;
+    info!("Head of batch {} was signed", batch_id);
+    Ok(())
+}
+
+pub(crate) fn export_head(state: &State, batch_id: &common::BatchId, path: &PathBuf) -> Result<()> {
+    let head = state
+        .batch_head_map
+        .get(batch_id)
+        .ok_or(anyhow::Error::msg("the head of this batch was not signed"))?;
+    std::fs::write(path, serde_json::to_string_pretty(head)?)?;
+    info!("Head of batch {} was exported", batch_id);
+    Ok(())
+}
+
+pub(crate) fn verify_head(
+    state: &mut State,
+    path: &PathBuf,
+    public_key: Option<&str>,
+    import: bool,
+) -> Result<()> {
+    let signed: SignedTreeHead = serde_json::from_slice(&std::fs::read(path)?)?;
+    let head = &signed.head;
+
+    // Without the key of the signer, a head only proves that it was signed by
+    // whoever made it, so it cannot be trusted for downloads.
+    if import && public_key.is_none() {
+        return Err(anyhow::Error::msg(
+            "Pass the --public-key of the signer to import a head",
+        ));
+    }
+    match public_key {
+        Some(public_key) => {
+            if !signed.is_signed_by(&hex::decode(public_key)?) {
+                return Err(anyhow::Error::msg("Signature of the head is not valid"));
+            }
+            info!(
+                "Head of batch {} ({} files) was signed by {}",
+                head.batch_id,
+                head.size,
+                hex::encode(&signed.public_key)
+            );
+        }
+        None => {
+            if !signed.verify() {
+                return Err(anyhow::Error::msg("Signature of the head is not valid"));
+            }
+            info!(
+                "Signature of the head of batch {} ({} files) is self-consistent, \
+                 pass --public-key to check who signed it",
+                head.batch_id, head.size
+            );
+        }
+    }
+
+    let batch_id: common::BatchId = head.batch_id.parse().map_err(anyhow::Error::msg)?;
+    match state.batch_root_map.get(&batch_id) {
+        Some(root_hash) => {
+            let size_matches = state
+                .batch_size_map
+                .get(&batch_id)
+                .map_or(true, |size| *size == head.size);
+            if !digests_match(root_hash, &head.root_hash) || !size_matches {
+                return Err(anyhow::Error::msg(
+                    "The head does not match the batch in the state",
+                ));
+            }
+            info!("Head matches the batch in the state");
+        }
+        None if import => {
+            if !std::ptr::eq(head.algorithm, common::DIGEST) {
+                return Err(anyhow::Error::msg("The head uses another hash algorithm"));
+            }
+            state
+                .batch_root_map
+                .insert(batch_id.clone(), head.root_hash.clone());
+            state.batch_size_map.insert(batch_id.clone(), head.size);
+            state.batch_head_map.insert(batch_id, signed.clone());
+            state.save_state()?;
+            info!("Head was imported into the state");
+        }
+        None => {
+            info!("Batch is not in the state, pass --import with --public-key to trust this head")
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use crate::{commands::ProofFormat, download::verify_proof, state::State};
+use anyhow::Result;
+use merkle_tree::{
+    leaf::FileLeaf,
+    proof::Proof,
+    render::{proof_to_ascii, proof_to_dot},
+};
+use std::path::PathBuf;
+use tracing::info;
+
+pub(crate) fn inspect_proof(
+    state: &State,
+    path: &PathBuf,
+    format: ProofFormat,
+    batch_id: Option<&common::BatchId>,
+) -> Result<()> {
+    let proof: Proof<FileLeaf> = serde_json::from_slice(&std::fs::read(path)?)?;
+
+    match format {
+        ProofFormat::Ascii => print!("{}", proof_to_ascii(&proof)),
+        ProofFormat::Dot => print!("{}", proof_to_dot(&proof)),
+    }
+
+    if let Some(batch_id) = batch_id {
+        verify_proof(state, batch_id, &proof)?;
+        info!(
+            "Proof of {} ({} bytes) is valid for batch {}",
+            proof.value.path, proof.value.size, batch_id
+        );
+    }
+
+    Ok(())
+}
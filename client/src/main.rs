@@ -9,8 +9,15 @@ use state::State;
 use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
 
+mod append;
+mod audit;
 mod commands;
+mod content;
+mod delete;
 mod download;
+mod error;
+mod head;
+mod inspect;
 mod state;
 mod upload;
 
@@ -50,19 +57,77 @@ async fn main() -> Result<()> {
     let client = Client::new();
 
     match &cli.command {
-        Some(Commands::UploadBatch { batch_id, paths }) => {
-            upload::upload_files(client.clone(), &mut state, addr, batch_id.clone(), paths).await?;
+        Some(Commands::UploadBatch {
+            batch_id,
+            paths,
+            keyed,
+            replace,
+        }) => {
+            upload::upload_files(
+                client.clone(),
+                &mut state,
+                addr,
+                batch_id.clone(),
+                paths,
+                *keyed,
+                *replace,
+            )
+            .await?;
             Ok(())
         }
+        Some(Commands::AppendFiles { batch_id, paths }) => {
+            append::append_files(client, &mut state, addr, batch_id, paths).await
+        }
         Some(Commands::DownloadFile {
             batch_id,
             file_index,
-            destination_path,
+            destination_dir,
+            proof_path,
+            keep_special_bits,
         }) => {
-            download::download_file(client, &state, addr, destination_path, file_index, batch_id)
-                .await?;
+            download::download_file(
+                client,
+                &state,
+                addr,
+                download::Destination {
+                    dir: destination_dir,
+                    keep_special_bits: *keep_special_bits,
+                },
+                file_index,
+                batch_id,
+                proof_path.as_ref(),
+            )
+            .await?;
             Ok(())
         }
+        Some(Commands::DeleteBatch { batch_id }) => {
+            delete::delete_batch(client, &mut state, addr, batch_id).await
+        }
+        Some(Commands::DeleteFile {
+            batch_id,
+            file_index,
+        }) => delete::delete_file(client, &state, addr, batch_id, *file_index).await,
+        Some(Commands::InspectProof {
+            path,
+            format,
+            batch_id,
+        }) => inspect::inspect_proof(&state, path, *format, batch_id.as_ref()),
+        Some(Commands::GenerateSigningKey { key_path }) => head::generate_signing_key(key_path),
+        Some(Commands::SignHead { batch_id, key_path }) => {
+            head::sign_head(&mut state, batch_id, key_path)
+        }
+        Some(Commands::ExportHead { batch_id, path }) => head::export_head(&state, batch_id, path),
+        Some(Commands::VerifyHead {
+            path,
+            public_key,
+            import,
+        }) => head::verify_head(&mut state, path, public_key.as_deref(), *import),
+        Some(Commands::AuditLog { batch_id }) => {
+            audit::audit_log(client, &mut state, addr, batch_id).await
+        }
         None => Ok(()),
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -1,8 +1,10 @@
 use anyhow::Result;
+use merkle_tree::{hashing::hmac_algorithm, head::SignedTreeHead, log::LogHead};
+use ring::hmac;
 use serde_derive::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
-    fs::File,
+    fs::{File, OpenOptions},
     io::{Read, Write},
     path::PathBuf,
 };
@@ -13,9 +15,29 @@ pub const DEFAULT_STATE_PATH: &str = "state.json";
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub(crate) struct State {
     pub batch_root_map: BTreeMap<common::BatchId, Vec<u8>>,
+    /// The secret keys of keyed batches
+    #[serde(default)]
+    pub batch_key_map: BTreeMap<common::BatchId, Vec<u8>>,
+    /// The number of files of every batch
+    #[serde(default)]
+    pub batch_size_map: BTreeMap<common::BatchId, u64>,
+    /// The signed heads of batches, see `sign-head`
+    #[serde(default)]
+    pub batch_head_map: BTreeMap<common::BatchId, SignedTreeHead>,
+    /// The head of the log of the server at the last audit, see `audit-log`
+    #[serde(default)]
+    pub log_head: Option<LogHead>,
 }
 
 impl State {
+    /// Returns the HMAC key of the given batch, if it is keyed.
+    pub(crate) fn batch_key(&self, batch_id: &common::BatchId) -> Option<hmac::Key> {
+        let algorithm = hmac_algorithm(common::DIGEST)?;
+        self.batch_key_map
+            .get(batch_id)
+            .map(|key| hmac::Key::new(algorithm, key))
+    }
+
     pub(crate) fn load_state() -> Result<State> {
         let path = PathBuf::from(
             std::env::var(STATE_PATH)
@@ -53,7 +75,21 @@ impl State {
                 .unwrap_or(DEFAULT_STATE_PATH.to_string()),
         );
         let serialized = serde_json::to_string(self)?;
-        let mut file = File::create(path)?;
+        // The state holds the keys of keyed batches, so only the user may read it.
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        // A state file created before may still be readable by others.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
         file.write_all(serialized.as_bytes())?;
         Ok(())
     }
@@ -0,0 +1,217 @@
+use crate::{
+    append::check_append_receipt,
+    content::CheckedReader,
+    upload::{check_receipt, file_parts},
+};
+use common::{AppendReceipt, BatchId, ReceiptLeaf, UploadReceipt};
+use merkle_tree::{leaf::FileLeaf, merkletree::MerkleTree};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncReadExt;
+
+/// Writes `files` to `dir`, and returns their paths in the same order.
+fn write_files(dir: &Path, files: &[(&str, &[u8])]) -> Vec<PathBuf> {
+    files
+        .iter()
+        .map(|(name, content)| {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            path
+        })
+        .collect()
+}
+
+/// Builds the receipt that an honest server answers for `tree`.
+fn receipt(batch_id: &BatchId, tree: &MerkleTree<FileLeaf>) -> UploadReceipt {
+    UploadReceipt {
+        batch_id: batch_id.clone(),
+        algorithm: tree.algorithm,
+        count: tree.count() as u64,
+        root_hash: tree.root_hash().clone(),
+        leaves: tree
+            .leaves()
+            .map(|leaf| ReceiptLeaf {
+                path: leaf.value.path.clone(),
+                leaf_hash: leaf.hash.to_vec(),
+            })
+            .collect(),
+    }
+}
+
+/// Changes a receipt, and names what the check reports about it.
+type Tamper<'a, T> = (&'a dyn Fn(&mut T), &'a str);
+
+fn tree(leaves: Vec<FileLeaf>) -> MerkleTree<FileLeaf> {
+    MerkleTree::from_vec_with_profile(common::DIGEST, common::PROFILE, leaves)
+}
+
+#[tokio::test]
+async fn test_file_parts_are_in_canonical_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = write_files(
+        dir.path(),
+        &[
+            ("b", b"second"),
+            ("a", b"first"),
+            ("B", b"upper"),
+            ("é", b""),
+        ],
+    );
+
+    let (parts, leaves) = file_parts(&paths, None).await.unwrap();
+    let names: Vec<_> = parts.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["B", "a", "b", "é"]);
+    let leaf_paths: Vec<_> = leaves.iter().map(|leaf| leaf.path.as_str()).collect();
+    assert_eq!(leaf_paths, names);
+    assert!(FileLeaf::is_canonical_order(&leaves));
+
+    // The leaves, and so the root, are the same for any order of the arguments.
+    let root = tree(leaves.clone()).root_hash().clone();
+    for order in [[3, 2, 1, 0], [1, 3, 0, 2], [2, 0, 3, 1]] {
+        let shuffled: Vec<_> = order.iter().map(|&i| paths[i].clone()).collect();
+        let (_, other) = file_parts(&shuffled, None).await.unwrap();
+        assert_eq!(other, leaves);
+        assert_eq!(tree(other).root_hash(), &root);
+    }
+}
+
+#[tokio::test]
+async fn test_file_parts_refuse_duplicate_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = write_files(
+        dir.path(),
+        &[("x/a", b"first"), ("y/a", b"second"), ("b", b"third")],
+    );
+    let err = file_parts(&paths, None).await.unwrap_err();
+    assert!(err.to_string().contains("two files are named a"), "{}", err);
+    let err = file_parts(&[paths[2].clone(), paths[2].clone()], None)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("two files are named b"), "{}", err);
+    assert!(file_parts(&[dir.path().join("missing")], None)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_checked_reader_detects_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file");
+    std::fs::write(&path, b"hello").unwrap();
+    let (_, leaves) = file_parts(&[path.clone()], None).await.unwrap();
+
+    let mut content = vec![];
+    let file = tokio::fs::File::open(&path).await.unwrap();
+    CheckedReader::new(file, leaves[0].clone(), None)
+        .read_to_end(&mut content)
+        .await
+        .unwrap();
+    assert_eq!(content, b"hello");
+
+    // A file modified after its leaf was built fails at its end, whatever its size.
+    for changed in [&b"jello"[..], b"hello, world"] {
+        std::fs::write(&path, changed).unwrap();
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let err = CheckedReader::new(file, leaves[0].clone(), None)
+            .read_to_end(&mut vec![])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("file changed"), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_check_receipt_refuses_tampered_receipts() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = write_files(dir.path(), &[("a", b"first"), ("b", b"second")]);
+    let (_, leaves) = file_parts(&paths, None).await.unwrap();
+    let tree = tree(leaves);
+    let batch_id: BatchId = "batch".parse().unwrap();
+    let honest = receipt(&batch_id, &tree);
+    check_receipt(&honest, &batch_id, &tree).unwrap();
+
+    let tampered: [Tamper<UploadReceipt>; 6] = [
+        (
+            &|receipt| receipt.batch_id = "other".parse().unwrap(),
+            "recorded batch other",
+        ),
+        (&|receipt| receipt.leaves.swap(0, 1), "differ: a (0), b (1)"),
+        (
+            &|receipt| receipt.leaves[1].leaf_hash[0] ^= 1,
+            "differ: b (1)",
+        ),
+        (
+            &|receipt| receipt.leaves[0].path = "c".to_string(),
+            "differ: a (0)",
+        ),
+        (&|receipt| drop(receipt.leaves.pop()), "differ: b (1)"),
+        (&|receipt| receipt.root_hash[0] ^= 1, "another root"),
+    ];
+    for (tamper, expected) in tampered {
+        let mut receipt = honest.clone();
+        tamper(&mut receipt);
+        let err = check_receipt(&receipt, &batch_id, &tree).unwrap_err();
+        assert!(err.to_string().contains(expected), "{}", err);
+    }
+    let mut receipt = honest;
+    receipt.count += 1;
+    assert!(check_receipt(&receipt, &batch_id, &tree).is_err());
+}
+
+#[tokio::test]
+async fn test_check_append_receipt_refuses_tampered_receipts() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = write_files(
+        dir.path(),
+        &[("a", b"first"), ("b", b"second"), ("C", b"third")],
+    );
+    let (_, old_leaves) = file_parts(&paths[..2], None).await.unwrap();
+    let (_, appended) = file_parts(&paths[2..], None).await.unwrap();
+    let old_tree = tree(old_leaves.clone());
+    let old_root = old_tree.root_hash().clone();
+    let new_tree = tree([old_leaves, appended.clone()].concat());
+    let batch_id: BatchId = "batch".parse().unwrap();
+    let honest = AppendReceipt {
+        receipt: receipt(&batch_id, &new_tree),
+        proof: new_tree.gen_consistency_proof(2).unwrap(),
+    };
+    check_append_receipt(&honest, &batch_id, 2, &old_root, &appended).unwrap();
+
+    let other_tree = tree(appended.clone());
+    let tampered: [Tamper<AppendReceipt>; 6] = [
+        (
+            &|append| append.receipt.batch_id = "other".parse().unwrap(),
+            "another batch",
+        ),
+        (&|append| append.receipt.count += 1, "another batch"),
+        (
+            &|append| append.receipt.leaves[2].leaf_hash[0] ^= 1,
+            "differ: C (2)",
+        ),
+        (
+            &|append| append.receipt.leaves[0].leaf_hash[0] ^= 1,
+            "not the root of its leaves",
+        ),
+        (
+            &|append| append.receipt.root_hash[0] ^= 1,
+            "not the root of its leaves",
+        ),
+        (
+            &|append| append.proof = other_tree.gen_consistency_proof(1).unwrap(),
+            "did not prove",
+        ),
+    ];
+    for (tamper, expected) in tampered {
+        let mut append = honest.clone();
+        tamper(&mut append);
+        let err = check_append_receipt(&append, &batch_id, 2, &old_root, &appended).unwrap_err();
+        assert!(err.to_string().contains(expected), "{}", err);
+    }
+
+    // The proof must extend the root that the client knows.
+    let mut other_root = old_root.clone();
+    other_root[0] ^= 1;
+    let err = check_append_receipt(&honest, &batch_id, 2, &other_root, &appended).unwrap_err();
+    assert!(err.to_string().contains("did not prove"), "{}", err);
+    assert!(check_append_receipt(&honest, &batch_id, 1, &old_root, &appended).is_err());
+}
@@ -1,45 +1,78 @@
-use crate::state::State;
+use crate::{
+    content::{CheckedReader, ContentHasher},
+    error::check_response,
+    state::State,
+};
 use anyhow::Result;
-use merkle_tree::merkletree::MerkleTree;
+use common::{BatchId, UploadReceipt};
+use merkle_tree::{leaf::FileLeaf, merkletree::MerkleTree, proof::digests_match};
 use reqwest::{
     multipart::{self, Part},
-    Body, Client,
+    Body, Client, StatusCode,
+};
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{
+    collections::BTreeSet,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
 };
-use std::{net::SocketAddr, path::PathBuf};
 use tokio::io::AsyncReadExt;
 use tokio_util::codec::{BytesCodec, FramedRead};
 use tracing::info;
 
-pub(crate) async fn upload_files(
-    client: Client,
-    state: &mut State,
-    addr: SocketAddr,
-    batch_id: common::BatchId,
-    file_name_vec: &[PathBuf],
-) -> Result<()> {
-    let part_vec: Vec<_> = file_name_vec
-        .iter()
-        .map(move |file_name| async {
-            let mut file = tokio::fs::File::open(file_name.clone()).await?;
+/// Builds the leaf of the file at `path`, which is stored under `name`.
+/// The content hash is keyed if `key` is given.
+async fn file_leaf(path: &Path, name: String, key: Option<&hmac::Key>) -> Result<FileLeaf> {
+    let metadata = tokio::fs::metadata(path).await?;
 
-            let mut bytes = Vec::new();
-            #[allow(clippy::let_underscore_future)]
-            let _ = file.read_to_end(&mut bytes);
-            let stream = FramedRead::new(file, BytesCodec::new());
-            let file_body = Body::wrap_stream(stream);
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = ContentHasher::new(key);
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
 
-            // Each file needs a form
-            let some_file = multipart::Part::stream(file_body)
-                .file_name(
-                    file_name
-                        .file_name()
-                        .ok_or(anyhow::Error::msg("not a filename"))?
-                        .to_str()
-                        .ok_or(anyhow::Error::msg("not a valid str"))?
-                        .to_string(),
-                )
-                .mime_str("application/octet-stream")?;
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+        .map(|mtime| mtime.as_secs());
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode() & 0o7777)
+    };
+    #[cfg(not(unix))]
+    let mode = None;
 
+    Ok(FileLeaf {
+        path: name,
+        size,
+        mtime,
+        mode,
+        content_hash: hasher.finish(),
+    })
+}
+
+/// Builds the multipart parts of the files at `paths`, named after them,
+/// and their leaves, both in canonical order.
+pub(crate) async fn file_parts(
+    paths: &[PathBuf],
+    key: Option<&hmac::Key>,
+) -> Result<(Vec<(String, Part)>, Vec<FileLeaf>)> {
+    let part_vec: Vec<_> = paths
+        .iter()
+        .map(|file_name| async {
             let part_name = file_name
                 .file_name()
                 .ok_or(anyhow::Error::msg("not a filename"))?
@@ -47,44 +80,179 @@ pub(crate) async fn upload_files(
                 .ok_or(anyhow::Error::msg("not a valid str"))?
                 .to_string();
 
-            Ok::<(Part, Vec<u8>, String), anyhow::Error>((some_file, bytes, part_name))
+            // Files are read twice, as the manifest that precedes them lists their hashes.
+            let leaf = file_leaf(file_name, part_name.clone(), key).await?;
+
+            let file = tokio::fs::File::open(file_name.clone()).await?;
+            let reader = CheckedReader::new(file, leaf.clone(), key);
+            let stream = FramedRead::new(reader, BytesCodec::new());
+            let file_body = Body::wrap_stream(stream);
+
+            // Each file needs a form
+            let some_file = multipart::Part::stream(file_body)
+                .file_name(part_name.clone())
+                .mime_str("application/octet-stream")?;
+
+            Ok::<(Part, FileLeaf, String), anyhow::Error>((some_file, leaf, part_name))
         })
         .collect();
 
-    // Create the multipart form
-    let mut form = multipart::Form::new();
-
-    let mut bytes_vec = vec![];
+    let mut files = vec![];
+    let mut names = BTreeSet::new();
 
     for tuple in part_vec.into_iter() {
-        let (part, bytes, part_name) = tuple.await?;
+        let (part, leaf, part_name) = tuple.await?;
 
-        form = form.part(part_name, part);
+        if !names.insert(part_name.clone()) {
+            return Err(anyhow::Error::msg(format!(
+                "two files are named {}",
+                part_name
+            )));
+        }
+        files.push((part_name, part, leaf));
+    }
 
-        bytes_vec.push(bytes);
+    // Files are indexed in canonical order, whatever the order of the arguments,
+    // as the server builds its tree in the same order.
+    files.sort_by(|(_, _, a), (_, _, b)| a.cmp_canonical(b));
+    let mut parts = vec![];
+    let mut leaves = vec![];
+    for (part_name, part, leaf) in files {
+        parts.push((part_name, part));
+        leaves.push(leaf);
     }
+    Ok((parts, leaves))
+}
 
-    let tree = MerkleTree::from_vec(common::DIGEST, bytes_vec.clone());
+pub(crate) async fn upload_files(
+    client: Client,
+    state: &mut State,
+    addr: SocketAddr,
+    batch_id: BatchId,
+    file_name_vec: &[PathBuf],
+    keyed: bool,
+    replace: bool,
+) -> Result<()> {
+    // The key of a batch is kept unless it is replaced,
+    // so that uploading the same files again changes nothing.
+    let has_key = state.batch_key_map.contains_key(&batch_id);
+    if keyed && (replace || !has_key) {
+        let mut key_value = vec![0; common::DIGEST.output_len];
+        SystemRandom::new()
+            .fill(&mut key_value)
+            .map_err(|_| anyhow::Error::msg("could not generate a key"))?;
+        state.batch_key_map.insert(batch_id.clone(), key_value);
+    } else if !keyed {
+        state.batch_key_map.remove(&batch_id);
+    }
+    let key = state.batch_key(&batch_id);
 
-    state
-        .batch_root_map
-        .insert(batch_id.clone(), tree.root_hash().clone());
+    let (parts, leaves) = file_parts(file_name_vec, key.as_ref()).await?;
+
+    // The server builds the tree from the manifest, but cannot compute the
+    // hashes of a keyed tree: it gets the tree itself instead.
+    let tree = match key {
+        Some(ref key) => MerkleTree::from_vec_keyed(key, common::PROFILE, leaves.clone()),
+        None => MerkleTree::from_vec_with_profile(common::DIGEST, common::PROFILE, leaves.clone()),
+    };
+    let first_part = if keyed {
+        (common::TREE_FIELD, serde_json::to_string(&tree)?)
+    } else {
+        (common::MANIFEST_FIELD, serde_json::to_string(&leaves)?)
+    };
+
+    // Create the multipart form, the manifest or the tree first
+    let mut form = multipart::Form::new().part(
+        first_part.0,
+        Part::text(first_part.1).mime_str("application/json")?,
+    );
+    for (part_name, part) in parts {
+        form = form.part(part_name, part);
+    }
 
     // Send the request
-    let _ = client
+    let response = client
         .post(format!(
-            "http://{}{}?batch_id={}",
+            "http://{}{}?batch_id={}&replace={}",
             addr,
             common::UPLOAD_ROUTE,
-            batch_id
+            batch_id,
+            replace
         ))
         .multipart(form)
         .send()
         .await?;
-    info!("Request was sent");
+    let response = check_response(response).await?;
+    if response.status() == StatusCode::OK {
+        info!("Server already had this batch");
+    } else {
+        info!("Batch was uploaded");
+    }
+    let receipt: UploadReceipt = response.json().await?;
+    check_receipt(&receipt, &batch_id, &tree)?;
+    info!("Server computed the same root");
+    for (index, leaf) in leaves.iter().enumerate() {
+        info!("File {} is {}", index, leaf.path);
+    }
+
+    state
+        .batch_root_map
+        .insert(batch_id.clone(), tree.root_hash().clone());
+    state
+        .batch_size_map
+        .insert(batch_id.clone(), tree.count() as u64);
+    state.batch_head_map.remove(&batch_id);
 
     state.save_state()?;
     info!("State was updated");
 
     Ok(())
 }
+
+/// Checks that the server built the same tree as `tree`,
+/// and lists the files whose leaves differ otherwise.
+pub(crate) fn check_receipt(
+    receipt: &UploadReceipt,
+    batch_id: &BatchId,
+    tree: &MerkleTree<FileLeaf>,
+) -> Result<()> {
+    if receipt.batch_id != *batch_id || !std::ptr::eq(receipt.algorithm, tree.algorithm) {
+        return Err(anyhow::Error::msg(format!(
+            "The server recorded batch {} hashed with {:?} instead, the batch was not recorded",
+            receipt.batch_id, receipt.algorithm
+        )));
+    }
+
+    let leaves: Vec<_> = tree.leaves().collect();
+    let mut differing = vec![];
+    for index in 0..leaves.len().max(receipt.leaves.len()) {
+        let ours = leaves.get(index);
+        let theirs = receipt.leaves.get(index);
+        let matches = match (ours, theirs) {
+            (Some(ours), Some(theirs)) => {
+                ours.value.path == theirs.path && digests_match(ours.hash, &theirs.leaf_hash)
+            }
+            _ => false,
+        };
+        if !matches {
+            let path = ours
+                .map(|leaf| leaf.value.path.as_str())
+                .or(theirs.map(|leaf| leaf.path.as_str()))
+                .unwrap_or_default();
+            differing.push(format!("{} ({})", path, index));
+        }
+    }
+    if !differing.is_empty() {
+        return Err(anyhow::Error::msg(format!(
+            "The server built another tree, the batch was not recorded. Files that differ: {}",
+            differing.join(", ")
+        )));
+    }
+    if receipt.count != tree.count() as u64 || !digests_match(&receipt.root_hash, tree.root_hash())
+    {
+        return Err(anyhow::Error::msg(
+            "The server computed another root, the batch was not recorded",
+        ));
+    }
+    Ok(())
+}
@@ -1,13 +1,161 @@
+use merkle_proof::{consistency::ConsistencyProof, hashing::Profile, proof::algorithm_serde};
 use ring::digest::{Algorithm, SHA512};
+use serde_derive::{Deserialize, Serialize};
+use std::{borrow::Borrow, fmt, ops::Deref, str::FromStr};
 
-pub type BatchId = String;
+/// The name of a batch, which is also used as a path component in storage.
+///
+/// It is 1 to 128 ASCII letters, digits, `-`, `_` and `.`, and does not start with `.`,
+/// so that it is neither a relative component nor a hidden name such as `.catalog`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BatchId(String);
+
+impl BatchId {
+    pub const MAX_LEN: usize = 128;
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for BatchId {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let valid = !value.is_empty()
+            && value.len() <= BatchId::MAX_LEN
+            && !value.starts_with('.')
+            && value
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.'));
+        if !valid {
+            return Err(format!(
+                "invalid batch id {:?}: use 1 to {} letters, digits, '-', '_' or '.', not starting with '.'",
+                value,
+                BatchId::MAX_LEN
+            ));
+        }
+        Ok(BatchId(value))
+    }
+}
+
+impl FromStr for BatchId {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        BatchId::try_from(value.to_string())
+    }
+}
+
+impl From<BatchId> for String {
+    fn from(batch_id: BatchId) -> Self {
+        batch_id.0
+    }
+}
+
+impl Deref for BatchId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for BatchId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for BatchId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// What went wrong with a request, as reported by the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// No such batch, file or log entry, or the file was deleted
+    NotFound,
+    /// The request is malformed, or does not match its manifest
+    BadRequest,
+    /// The batch already exists
+    Conflict,
+    /// The request lacks the delete token of the server
+    Unauthorized,
+    /// The upload exceeds the size limit of the server
+    TooLarge,
+    /// The server failed, e.g. to reach its storage
+    Internal,
+}
+
+/// The JSON body of every error response of the server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+/// The leaf of a file in an `UploadReceipt`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptLeaf {
+    pub path: String,
+    pub leaf_hash: Vec<u8>,
+}
+
+/// The JSON body of a successful upload: the tree the server built,
+/// which the client checks against its own before recording the batch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UploadReceipt {
+    pub batch_id: BatchId,
+    #[serde(with = "algorithm_serde")]
+    pub algorithm: &'static Algorithm,
+    pub count: u64,
+    pub root_hash: Vec<u8>,
+    /// The leaves of the tree, by increasing index
+    pub leaves: Vec<ReceiptLeaf>,
+}
+
+/// The JSON body of a successful append: the receipt of the extended batch,
+/// and the proof that its tree extends the tree of the batch before the append.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AppendReceipt {
+    pub receipt: UploadReceipt,
+    pub proof: ConsistencyProof,
+}
 
 pub static DIGEST: &Algorithm = &SHA512;
+/// The hashing profile of batch trees.
+pub const PROFILE: Profile = Profile::Hardened;
 
 pub const PROOF_HEADER: &str = "proof";
+/// Name of the multipart field listing the uploaded files, sent before them.
+pub const MANIFEST_FIELD: &str = "manifest";
+/// Name of the multipart field holding the tree of a keyed batch, sent instead of the manifest.
+pub const TREE_FIELD: &str = "tree";
 pub const PORT: &str = "PORT";
 pub const DEFAULT_PORT: u64 = 8080;
 pub const ADDRESS: &str = "ADDRESS";
 pub const DEFAULT_ADDRESS: &str = "0.0.0.0";
 pub const UPLOAD_ROUTE: &str = "/upload";
 pub const DOWNLOAD_ROUTE: &str = "/download";
+/// Route to append files to the batch `:batch_id`.
+pub const APPEND_ROUTE: &str = "/batches/:batch_id/files";
+/// Route to delete the batch `:batch_id`.
+pub const BATCH_ROUTE: &str = "/batches/:batch_id";
+/// Route to delete the file `:file_index` of the batch `:batch_id`.
+pub const FILE_ROUTE: &str = "/batches/:batch_id/files/:file_index";
+/// The bearer token that delete requests must carry, known to both the server and the client.
+pub const DELETE_TOKEN: &str = "DELETE_TOKEN";
+/// Route of the current head of the log of batch roots.
+pub const LOG_HEAD_ROUTE: &str = "/log/head";
+/// Route of the proof that the latest root of a batch is in the log.
+pub const LOG_INCLUSION_ROUTE: &str = "/log/inclusion";
+/// Route of the proof that an older log is a prefix of a newer one.
+pub const LOG_CONSISTENCY_ROUTE: &str = "/log/consistency";
+
+#[cfg(test)]
+mod tests;
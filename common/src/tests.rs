@@ -0,0 +1,45 @@
+use crate::BatchId;
+
+#[test]
+fn test_batch_id() {
+    for valid in [
+        "photos",
+        "2023-05_backup.v2",
+        "a",
+        &"x".repeat(BatchId::MAX_LEN),
+    ] {
+        let batch_id: BatchId = valid.parse().unwrap();
+        assert_eq!(batch_id.as_str(), valid);
+        assert_eq!(batch_id.to_string(), valid);
+    }
+
+    for hostile in [
+        "",
+        ".",
+        "..",
+        "../../etc",
+        "/etc/passwd",
+        "a/b",
+        "a\\b",
+        ".catalog",
+        ".hidden",
+        "a b",
+        "caf\u{e9}",
+        "nul\0",
+        "%2e%2e",
+        &"x".repeat(BatchId::MAX_LEN + 1),
+    ] {
+        assert!(hostile.parse::<BatchId>().is_err(), "{:?}", hostile);
+    }
+}
+
+#[test]
+fn test_batch_id_serde() {
+    let batch_id: BatchId = "photos".parse().unwrap();
+    assert_eq!(serde_json::to_string(&batch_id).unwrap(), "\"photos\"");
+    assert_eq!(
+        serde_json::from_str::<BatchId>("\"photos\"").unwrap(),
+        batch_id
+    );
+    assert!(serde_json::from_str::<BatchId>("\"../photos\"").is_err());
+}
@@ -0,0 +1,117 @@
+use crate::hashing::{Hashing, Profile};
+use crate::proof::{algorithm_serde, digests_match};
+use alloc::vec::Vec;
+use ring::digest::Algorithm;
+use serde_derive::{Deserialize, Serialize};
+
+/// A `ConsistencyProof` proves that the tree with `old_size` leaves is a prefix
+/// of the tree with `new_size` leaves, i.e. that the newer tree was only appended to.
+///
+/// Both trees must have been built with `algorithm` and `profile`, and not be keyed.
+/// The proof has the format of RFC 9162, section 2.1.4, except that with the `Hardened`
+/// profile, the top hash of an older tree whose size is a power of two is not left out,
+/// as it cannot be recovered from its root hash.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsistencyProof {
+    /// The hashing algorithm of the trees
+    #[serde(with = "algorithm_serde")]
+    pub algorithm: &'static Algorithm,
+    /// The hashing profile of the trees
+    #[serde(default)]
+    pub profile: Profile,
+    /// The number of leaves of the older tree
+    pub old_size: usize,
+    /// The number of leaves of the newer tree
+    pub new_size: usize,
+    /// The hashes of the subtrees needed to recompute both top hashes
+    pub hashes: Vec<Vec<u8>>,
+}
+
+impl ConsistencyProof {
+    /// Checks whether this proof shows that the tree with root hash `old_root`
+    /// is a prefix of the tree with root hash `new_root`.
+    ///
+    /// The empty tree is a prefix of every tree.
+    pub fn validate(&self, old_root: &[u8], new_root: &[u8]) -> bool {
+        let (old_size, new_size) = (self.old_size, self.new_size);
+        if old_size > new_size {
+            return false;
+        }
+        if old_size == 0 {
+            let empty_top = self.profile.hash_empty(self.algorithm);
+            let empty_root = self
+                .profile
+                .root_hash(self.algorithm, 0, empty_top.as_ref());
+            return self.hashes.is_empty() && digests_match(&empty_root, old_root);
+        }
+        if old_size == new_size {
+            return self.hashes.is_empty() && digests_match(old_root, new_root);
+        }
+
+        let (old_top, new_top) = match self.top_hashes(old_root) {
+            Some(tops) => tops,
+            None => return false,
+        };
+        let profile = self.profile;
+        digests_match(
+            &profile.root_hash(self.algorithm, old_size, &old_top),
+            old_root,
+        ) && digests_match(
+            &profile.root_hash(self.algorithm, new_size, &new_top),
+            new_root,
+        )
+    }
+
+    /// Recomputes the top hashes of the older and newer trees, for `0 < old_size < new_size`.
+    fn top_hashes(&self, old_root: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+        let hash_nodes =
+            |left: &[u8], right: &[u8]| self.algorithm.hash_nodes(&left, &right).as_ref().to_vec();
+
+        // When the older tree is a complete subtree of the newer one, its top hash
+        // is the first hash of the path, and is left out of the proof.
+        let mut hashes = self.hashes.iter().map(Vec::as_slice);
+        let old_top;
+        let first = if self.old_size.is_power_of_two() {
+            old_top = match self.profile {
+                Profile::Legacy => old_root.to_vec(),
+                // The top hash is not recoverable from the root: it must be in the proof.
+                Profile::Hardened => hashes.next()?.to_vec(),
+            };
+            old_top.as_slice()
+        } else {
+            hashes.next()?
+        };
+
+        let mut old_node = self.old_size - 1;
+        let mut new_node = self.new_size - 1;
+        while old_node & 1 == 1 {
+            old_node >>= 1;
+            new_node >>= 1;
+        }
+
+        let mut old_hash = first.to_vec();
+        let mut new_hash = first.to_vec();
+        for hash in hashes {
+            if new_node == 0 {
+                return None;
+            }
+            if old_node & 1 == 1 || old_node == new_node {
+                old_hash = hash_nodes(hash, &old_hash);
+                new_hash = hash_nodes(hash, &new_hash);
+                while old_node & 1 == 0 && old_node != 0 {
+                    old_node >>= 1;
+                    new_node >>= 1;
+                }
+            } else {
+                new_hash = hash_nodes(&new_hash, hash);
+            }
+            old_node >>= 1;
+            new_node >>= 1;
+        }
+
+        if new_node != 0 {
+            return None;
+        }
+        Some((old_hash, new_hash))
+    }
+}
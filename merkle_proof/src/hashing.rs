@@ -0,0 +1,337 @@
+use alloc::vec::Vec;
+use ring::digest::{self, digest, Algorithm, Context, Digest};
+use ring::hmac;
+use serde_derive::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+/// The type of values stored in a `MerkleTree` must implement
+/// this trait, in order for them to be able to be fed
+/// to a Ring `Context` when computing the hash of a leaf.
+///
+/// A default instance for types that already implements
+/// `AsRef<[u8]>` is provided.
+///
+/// ## Example
+///
+/// Here is an example of how to implement `Hashable` for a type
+/// that does not (or cannot) implement `AsRef<[u8]>`:
+///
+/// ```ignore
+/// impl Hashable for PublicKey {
+///     fn update_context(&self, context: &mut Context) {
+///         let bytes: Vec<u8> = self.to_bytes();
+///         context.update(&bytes);
+///     }
+/// }
+/// ```
+pub trait Hashable {
+    /// Update the given `context` with `self`.
+    ///
+    /// See `ring::digest::Context::update` for more information.
+    fn update_context(&self, context: &mut Context);
+}
+
+impl<T: AsRef<[u8]>> Hashable for T {
+    fn update_context(&self, context: &mut Context) {
+        context.update(self.as_ref());
+    }
+}
+
+/// Prefix of the data hashed for a leaf.
+pub const LEAF_TAG: u8 = 0x00;
+/// Prefix of the data hashed for an internal node.
+pub const NODE_TAG: u8 = 0x01;
+/// Data hashed for an empty tree, in the `Hardened` profile.
+pub const EMPTY_TAG: u8 = 0x02;
+/// Prefix of the data hashed for the root, in the `Hardened` profile.
+pub const ROOT_TAG: u8 = 0x03;
+
+pub trait Hashing {
+    /// Compute the hash of the empty string
+    fn hash_empty(&'static self) -> Digest;
+
+    /// Returns a `Context` primed for hashing a leaf, so that the leaf
+    /// content can be fed to it incrementally.
+    ///
+    /// Finishing the context after feeding it the bytes of `leaf`
+    /// yields the same digest as `hash_leaf(leaf)`.
+    fn leaf_context(&'static self) -> Context;
+
+    /// Compute the hash of the given leaf
+    fn hash_leaf<T>(&'static self, bytes: &T) -> Digest
+    where
+        T: Hashable;
+
+    /// Compute the hash of the leaf whose content is read from `reader`,
+    /// without holding the whole content in memory.
+    #[cfg(feature = "std")]
+    fn hash_leaf_reader<R>(&'static self, reader: R) -> io::Result<Digest>
+    where
+        R: Read;
+
+    /// Compute the hash of the concatenation of `left` and `right`.
+    fn hash_nodes<T>(&'static self, left: &T, right: &T) -> Digest
+    where
+        T: Hashable;
+}
+
+impl Hashing for Algorithm {
+    fn hash_empty(&'static self) -> Digest {
+        digest(self, &[])
+    }
+
+    fn leaf_context(&'static self) -> Context {
+        let mut ctx = Context::new(self);
+        ctx.update(&[LEAF_TAG]);
+        ctx
+    }
+
+    fn hash_leaf<T>(&'static self, leaf: &T) -> Digest
+    where
+        T: Hashable,
+    {
+        let mut ctx = self.leaf_context();
+        leaf.update_context(&mut ctx);
+        ctx.finish()
+    }
+
+    #[cfg(feature = "std")]
+    fn hash_leaf_reader<R>(&'static self, reader: R) -> io::Result<Digest>
+    where
+        R: Read,
+    {
+        let mut reader = HashingReader::new(reader, self.leaf_context());
+        io::copy(&mut reader, &mut io::sink())?;
+        Ok(reader.finish())
+    }
+
+    fn hash_nodes<T>(&'static self, left: &T, right: &T) -> Digest
+    where
+        T: Hashable,
+    {
+        let mut ctx = Context::new(self);
+        ctx.update(&[NODE_TAG]);
+        left.update_context(&mut ctx);
+        right.update_context(&mut ctx);
+        ctx.finish()
+    }
+}
+
+/// How the root of a tree is derived from its leaves and internal nodes.
+///
+/// Both profiles hash leaves and internal nodes with distinct prefixes,
+/// and promote a node without a sibling to the level above as is.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Profile {
+    /// The root is the hash of the top node, and the empty tree hashes
+    /// to the digest of the empty string.
+    #[default]
+    Legacy,
+    /// The empty tree hashes to the digest of `EMPTY_TAG`, and the root is
+    /// the digest of `ROOT_TAG`, the number of leaves as a big-endian `u64`,
+    /// and the hash of the top node.
+    ///
+    /// Committing to the number of leaves fixes the shape of the tree, so that
+    /// no list of leaves can produce the root of a list of a different length,
+    /// e.g. by passing off internal nodes as leaves.
+    Hardened,
+}
+
+impl Profile {
+    /// Returns the hash of the top node of an empty tree.
+    pub fn hash_empty(self, algorithm: &'static Algorithm) -> Digest {
+        match self {
+            Profile::Legacy => algorithm.hash_empty(),
+            Profile::Hardened => digest(algorithm, &[EMPTY_TAG]),
+        }
+    }
+
+    /// Returns the root hash of a tree with `count` leaves whose top node hashes to `top_hash`.
+    pub fn root_hash(
+        self,
+        algorithm: &'static Algorithm,
+        count: usize,
+        top_hash: &[u8],
+    ) -> Vec<u8> {
+        match self {
+            Profile::Legacy => top_hash.to_vec(),
+            Profile::Hardened => {
+                let mut ctx = Context::new(algorithm);
+                ctx.update(&[ROOT_TAG]);
+                ctx.update(&(count as u64).to_be_bytes());
+                ctx.update(top_hash);
+                ctx.finish().as_ref().to_vec()
+            }
+        }
+    }
+}
+
+/// Computes the hashes of the leaves and internal nodes of a tree.
+///
+/// Implemented by `&'static Algorithm` for plain digests, and by `hmac::Key`
+/// for keyed trees, whose hashes can only be computed by the holder of the key.
+pub trait TreeHasher {
+    /// The digest algorithm underlying the hashes
+    fn algorithm(&self) -> &'static Algorithm;
+
+    /// Compute the hash of the given leaf
+    fn leaf_hash<T: Hashable>(&self, leaf: &T) -> Vec<u8>;
+
+    /// Compute the hash of the node with children hashes `left` and `right`
+    fn node_hash(&self, left: &[u8], right: &[u8]) -> Vec<u8>;
+
+    /// Compute the hash of the top node of an empty tree
+    fn empty_hash(&self, profile: Profile) -> Vec<u8>;
+}
+
+impl TreeHasher for &'static Algorithm {
+    fn algorithm(&self) -> &'static Algorithm {
+        self
+    }
+
+    fn leaf_hash<T: Hashable>(&self, leaf: &T) -> Vec<u8> {
+        self.hash_leaf(leaf).as_ref().to_vec()
+    }
+
+    fn node_hash(&self, left: &[u8], right: &[u8]) -> Vec<u8> {
+        self.hash_nodes(&left, &right).as_ref().to_vec()
+    }
+
+    fn empty_hash(&self, profile: Profile) -> Vec<u8> {
+        profile.hash_empty(self).as_ref().to_vec()
+    }
+}
+
+/// Keyed hashing: every hash is an HMAC tag, so that a proof does not allow
+/// testing guesses of the content of a leaf without the key.
+///
+/// The tag of a leaf is computed over `LEAF_TAG` and the plain leaf hash,
+/// which allows reusing `Hashable`, that of a node over `NODE_TAG` and the
+/// children tags, and that of an empty tree over `EMPTY_TAG`, or nothing
+/// with the `Legacy` profile.
+impl TreeHasher for hmac::Key {
+    fn algorithm(&self) -> &'static Algorithm {
+        hmac::Key::algorithm(self).digest_algorithm()
+    }
+
+    fn leaf_hash<T: Hashable>(&self, leaf: &T) -> Vec<u8> {
+        let mut ctx = hmac::Context::with_key(self);
+        ctx.update(&[LEAF_TAG]);
+        ctx.update(TreeHasher::algorithm(self).hash_leaf(leaf).as_ref());
+        ctx.sign().as_ref().to_vec()
+    }
+
+    fn node_hash(&self, left: &[u8], right: &[u8]) -> Vec<u8> {
+        let mut ctx = hmac::Context::with_key(self);
+        ctx.update(&[NODE_TAG]);
+        ctx.update(left);
+        ctx.update(right);
+        ctx.sign().as_ref().to_vec()
+    }
+
+    fn empty_hash(&self, profile: Profile) -> Vec<u8> {
+        let data: &[u8] = match profile {
+            Profile::Legacy => &[],
+            Profile::Hardened => &[EMPTY_TAG],
+        };
+        hmac::sign(self, data).as_ref().to_vec()
+    }
+}
+
+/// Returns the HMAC algorithm based on the given digest algorithm, if any.
+pub fn hmac_algorithm(algorithm: &'static Algorithm) -> Option<hmac::Algorithm> {
+    [
+        hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+        hmac::HMAC_SHA256,
+        hmac::HMAC_SHA384,
+        hmac::HMAC_SHA512,
+    ]
+    .into_iter()
+    .find(|hmac| core::ptr::eq(hmac.digest_algorithm(), algorithm))
+}
+
+/// Returns the algorithm with the given name, as printed by its `Debug` implementation.
+pub fn algorithm_by_name(name: &str) -> Option<&'static Algorithm> {
+    match name {
+        "SHA1" => Some(&digest::SHA1_FOR_LEGACY_USE_ONLY),
+        "SHA256" => Some(&digest::SHA256),
+        "SHA384" => Some(&digest::SHA384),
+        "SHA512" => Some(&digest::SHA512),
+        "SHA512_256" => Some(&digest::SHA512_256),
+        _ => None,
+    }
+}
+
+/// Compute the hash of the leaf whose content is read from the asynchronous
+/// `reader`, without holding the whole content in memory.
+#[cfg(feature = "tokio")]
+pub async fn hash_leaf_async_reader<R>(
+    algorithm: &'static Algorithm,
+    reader: R,
+) -> io::Result<Digest>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut reader = HashingReader::new(reader, algorithm.leaf_context());
+    tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+    Ok(reader.finish())
+}
+
+/// A reader adapter that feeds every byte read through it to a Ring `Context`.
+///
+/// This allows hashing data while it is being streamed elsewhere,
+/// e.g. to disk or to the network.
+/// Implements `std::io::Read`, and `tokio::io::AsyncRead` when the `tokio` feature is enabled.
+///
+/// ## Example
+///
+/// ```ignore
+/// let mut reader = HashingReader::new(body, DIGEST.leaf_context());
+/// tokio::io::copy(&mut reader, &mut file).await?;
+/// let leaf_hash = reader.finish();
+/// ```
+#[cfg(feature = "std")]
+pub struct HashingReader<R> {
+    inner: R,
+    context: Context,
+}
+
+#[cfg(feature = "std")]
+impl<R> HashingReader<R> {
+    /// Wraps `inner`, feeding everything read from it to `context`.
+    pub fn new(inner: R, context: Context) -> Self {
+        HashingReader { inner, context }
+    }
+
+    /// Consumes the reader and returns the digest of everything read so far.
+    pub fn finish(self) -> Digest {
+        self.context.finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.context.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for HashingReader<R> {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let this = &mut *self;
+        std::task::ready!(std::pin::Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.context.update(&buf.filled()[filled..]);
+        std::task::Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,109 @@
+use crate::hashing::Hashable;
+use crate::proof::digests_match;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use ring::digest::{digest, Algorithm, Context};
+use serde_derive::{Deserialize, Serialize};
+
+/// A leaf describing a file: its path and metadata along with the hash of its content.
+///
+/// Hashing a `FileLeaf` commits to all of its fields, so that a proof for it
+/// also proves the name under which the file was stored, and not only its bytes.
+///
+/// The fields are fed to the hash in order, as follows:
+///
+/// * `path`, as its length in bytes (`u64`) followed by its UTF-8 bytes,
+/// * `size` (`u64`),
+/// * `mtime` and `mode`, each as a `0` byte if absent,
+///   or a `1` byte followed by the value (`u64` and `u32`),
+/// * `content_hash`, as its length in bytes (`u64`) followed by its bytes.
+///
+/// Integers are big-endian.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct FileLeaf {
+    /// The path of the file, relative to its batch, with `/` as separator
+    pub path: String,
+    /// The size of the file, in bytes
+    pub size: u64,
+    /// The last modification time of the file, in seconds since the Unix epoch
+    pub mtime: Option<u64>,
+    /// The Unix permission bits of the file
+    pub mode: Option<u32>,
+    /// The digest of the content of the file, see `ring::digest::digest`
+    pub content_hash: Vec<u8>,
+}
+
+impl FileLeaf {
+    /// Returns the bytes that are hashed for this leaf.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(40 + self.path.len() + self.content_hash.len());
+        self.write(|chunk| bytes.extend_from_slice(chunk));
+        bytes
+    }
+
+    /// Checks whether `content` is the content of this file: its size,
+    /// and its digest with `algorithm`, the algorithm of the tree.
+    /// The content hashes of keyed batches are HMAC tags, which never match.
+    pub fn matches_content(&self, algorithm: &'static Algorithm, content: &[u8]) -> bool {
+        content.len() as u64 == self.size
+            && digests_match(digest(algorithm, content).as_ref(), &self.content_hash)
+    }
+
+    /// Compares leaves in the canonical order of a batch: by `path`, byte by byte.
+    ///
+    /// Clients and servers sort the leaves of a batch this way, so that they build
+    /// the same tree whatever the order in which its files are given or sent,
+    /// and a leaf index designates the same file on both sides.
+    pub fn cmp_canonical(&self, other: &FileLeaf) -> Ordering {
+        self.path.as_bytes().cmp(other.path.as_bytes())
+    }
+
+    /// Checks whether `leaves` are in canonical order, see `cmp_canonical`,
+    /// with no two leaves for the same path.
+    pub fn is_canonical_order(leaves: &[FileLeaf]) -> bool {
+        leaves
+            .windows(2)
+            .all(|pair| pair[0].cmp_canonical(&pair[1]) == Ordering::Less)
+    }
+
+    /// Checks whether `path` is a relative path made of plain components only,
+    /// i.e. without `.`, `..`, empty components or backslashes,
+    /// so that it can safely be joined to a directory.
+    pub fn is_safe_path(path: &str) -> bool {
+        !path.is_empty()
+            && !path.contains('\\')
+            && !path.contains('\0')
+            && path
+                .split('/')
+                .all(|component| !matches!(component, "" | "." | ".."))
+    }
+
+    fn write<F: FnMut(&[u8])>(&self, mut write: F) {
+        write(&(self.path.len() as u64).to_be_bytes());
+        write(self.path.as_bytes());
+        write(&self.size.to_be_bytes());
+        match self.mtime {
+            None => write(&[0]),
+            Some(mtime) => {
+                write(&[1]);
+                write(&mtime.to_be_bytes());
+            }
+        }
+        match self.mode {
+            None => write(&[0]),
+            Some(mode) => {
+                write(&[1]);
+                write(&mode.to_be_bytes());
+            }
+        }
+        write(&(self.content_hash.len() as u64).to_be_bytes());
+        write(&self.content_hash);
+    }
+}
+
+impl Hashable for FileLeaf {
+    fn update_context(&self, context: &mut Context) {
+        self.write(|chunk| context.update(chunk));
+    }
+}
@@ -0,0 +1,18 @@
+//! Verification half of the `merkle_tree` crate: inclusion proofs and the
+//! leaf and node hashing they rely on.
+//!
+//! This crate is `no_std` and only needs `alloc`, so that proofs can be
+//! verified on `wasm32-unknown-unknown` and embedded targets.
+//! The `std` feature adds hashing from `std::io::Read`, and the `tokio`
+//! feature hashing from `tokio::io::AsyncRead`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod consistency;
+pub mod hashing;
+pub mod leaf;
+pub mod proof;
+
+#[cfg(test)]
+mod tests;
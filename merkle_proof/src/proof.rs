@@ -0,0 +1,282 @@
+use crate::hashing::{Hashable, Profile, TreeHasher};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+#[cfg(not(target_arch = "wasm32"))]
+use ring::constant_time;
+use ring::digest::Algorithm;
+use ring::hmac;
+use serde_derive::{Deserialize, Serialize};
+
+/// A `Proof` stucture contains all data to prove that some value is a member
+/// of a `MerkleTree` with root hash `root_hash`, and hash function `algorithm`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Proof<T> {
+    /// The hashing algorithm used in the original `MerkleTree`
+    #[serde(with = "algorithm_serde")]
+    pub algorithm: &'static Algorithm,
+    /// The hash of the root of the original `MerkleTree`
+    pub root_hash: Vec<u8>,
+    /// The first `Conjecture` of the `Proof`
+    pub conjecture: Conjecture,
+    /// The value concerned by this `Proof`
+    pub value: T,
+    /// The hashing profile of the original `MerkleTree`
+    #[serde(default)]
+    pub profile: Profile,
+    /// The number of leaves of the original `MerkleTree`, required by the `Hardened` profile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    /// Whether the hashes of the original `MerkleTree` are keyed, see `validate_with_key`
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub keyed: bool,
+}
+
+/// Serializes a `&'static Algorithm` as its name, e.g. `"SHA512"`.
+/// Use with `#[serde(with = "algorithm_serde")]`.
+pub mod algorithm_serde {
+    use crate::hashing::algorithm_by_name;
+    use alloc::format;
+    use alloc::string::String;
+    use ring::digest::Algorithm;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        algorithm: &'static Algorithm,
+        se: S,
+    ) -> Result<S::Ok, S::Error> {
+        format!("{:?}", algorithm).serialize(se)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<&'static Algorithm, D::Error> {
+        let alg_str: String = Deserialize::deserialize(de)?;
+        algorithm_by_name(&alg_str).ok_or_else(|| D::Error::custom("unknown hash algorithm"))
+    }
+}
+
+impl<T: PartialEq> PartialEq for Proof<T> {
+    fn eq(&self, other: &Proof<T>) -> bool {
+        self.root_hash == other.root_hash
+            && self.conjecture == other.conjecture
+            && self.value == other.value
+            && self.profile == other.profile
+            && self.count == other.count
+            && self.keyed == other.keyed
+    }
+}
+
+impl<T: Eq> Eq for Proof<T> {}
+
+impl<T: Ord> PartialOrd for Proof<T> {
+    fn partial_cmp(&self, other: &Proof<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Proof<T> {
+    fn cmp(&self, other: &Proof<T>) -> Ordering {
+        self.root_hash
+            .cmp(&other.root_hash)
+            .then(self.value.cmp(&other.value))
+            .then_with(|| self.conjecture.cmp(&other.conjecture))
+            .then(self.profile.cmp(&other.profile))
+            .then(self.count.cmp(&other.count))
+            .then(self.keyed.cmp(&other.keyed))
+    }
+}
+
+impl<T> Proof<T> {
+    /// Constructs a new `Proof`, for a tree with the `Legacy` profile.
+    /// See `with_profile` for other profiles.
+    pub fn new(
+        algorithm: &'static Algorithm,
+        root_hash: Vec<u8>,
+        conjecture: Conjecture,
+        value: T,
+    ) -> Self {
+        Proof {
+            algorithm,
+            root_hash,
+            conjecture,
+            value,
+            profile: Profile::Legacy,
+            count: None,
+            keyed: false,
+        }
+    }
+
+    /// Sets the hashing profile and the number of leaves of the tree this proof is for.
+    pub fn with_profile(mut self, profile: Profile, count: usize) -> Self {
+        self.profile = profile;
+        self.count = Some(count);
+        self
+    }
+
+    /// Checks whether this inclusion proof is well-formed,
+    /// and whether its root hash matches the given `root_hash`.
+    ///
+    /// With the `Hardened` profile, the path to the leaf must also
+    /// have the shape of a tree with `count` leaves.
+    /// Proofs for keyed trees never validate, see `validate_with_key`.
+    pub fn validate(&self, root_hash: &[u8]) -> bool {
+        !self.keyed && self.validate_with(root_hash, &self.algorithm)
+    }
+
+    /// Checks whether this inclusion proof for a keyed tree is well-formed,
+    /// and whether its root hash matches the given `root_hash`.
+    /// The key must be based on the proof's `algorithm`.
+    pub fn validate_with_key(&self, root_hash: &[u8], key: &hmac::Key) -> bool {
+        self.keyed
+            && core::ptr::eq(TreeHasher::algorithm(key), self.algorithm)
+            && self.validate_with(root_hash, key)
+    }
+
+    fn validate_with<H: TreeHasher>(&self, root_hash: &[u8], hasher: &H) -> bool {
+        if !digests_match(&self.root_hash, root_hash) {
+            return false;
+        }
+
+        let top_matches = match (self.profile, self.count) {
+            (Profile::Legacy, _) => digests_match(&self.conjecture.node_hash, root_hash),
+            (Profile::Hardened, Some(count)) => {
+                let expected =
+                    Profile::Hardened.root_hash(self.algorithm, count, &self.conjecture.node_hash);
+                self.conjecture.fits(count) && digests_match(&expected, root_hash)
+            }
+            (Profile::Hardened, None) => false,
+        };
+
+        top_matches && self.conjecture.validate(hasher)
+    }
+
+    /// Returns the hash of the leaf this proof is about.
+    pub fn leaf_hash(&self) -> &[u8] {
+        let mut conjecture = &self.conjecture;
+        while let Some(ref sub) = conjecture.sub_conjecture {
+            conjecture = sub;
+        }
+        &conjecture.node_hash
+    }
+
+    /// Checks whether this proof is about the leaf with hash `leaf_hash`.
+    /// This does not validate the proof itself, see `validate`.
+    pub fn is_for_leaf(&self, leaf_hash: &[u8]) -> bool {
+        digests_match(self.leaf_hash(), leaf_hash)
+    }
+
+    /// Checks whether this proof is about the leaf holding its `value`.
+    /// This does not validate the proof itself, see `validate`.
+    pub fn is_for_value(&self) -> bool
+    where
+        T: Hashable,
+    {
+        !self.keyed && self.is_for_leaf(&self.algorithm.leaf_hash(&self.value))
+    }
+
+    /// Checks whether this proof for a keyed tree is about the leaf holding its `value`.
+    /// This does not validate the proof itself, see `validate_with_key`.
+    pub fn is_for_value_with_key(&self, key: &hmac::Key) -> bool
+    where
+        T: Hashable,
+    {
+        self.keyed && self.is_for_leaf(&key.leaf_hash(&self.value))
+    }
+
+    /// Returns the index of this proof's value, given the total number of items in the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the proof is malformed. Call `validate` first.
+    pub fn index(&self, count: usize) -> usize {
+        self.conjecture.index(count)
+    }
+}
+
+/// A `Conjecture` holds the hash of a node, the hash of its sibling node,
+/// and a sub conjecture, whose `node_hash`, when combined with this `sibling_hash`
+/// must be equal to this `node_hash`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Conjecture {
+    pub node_hash: Vec<u8>,
+    pub sibling_hash: Option<Side<Vec<u8>>>,
+    pub sub_conjecture: Option<Box<Conjecture>>,
+}
+
+impl Conjecture {
+    /// Returns the index of this conjecture's value, given the total number of items in the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the conjecture is malformed. Call `validate_lemma` first.
+    pub fn index(&self, count: usize) -> usize {
+        let left_count = count.next_power_of_two() / 2;
+        match (self.sub_conjecture.as_ref(), self.sibling_hash.as_ref()) {
+            (None, None) => 0,
+            (Some(l), Some(&Side::Left(_))) => left_count + l.index(count - left_count),
+            (Some(l), Some(&Side::Right(_))) => l.index(left_count),
+            (None, Some(_)) | (Some(_), None) => panic!("malformed conjecture"),
+        }
+    }
+
+    /// Checks whether this conjecture follows a path from the top
+    /// to a leaf of a tree with `count` leaves.
+    fn fits(&self, count: usize) -> bool {
+        let left_count = count.next_power_of_two() / 2;
+        match (self.sub_conjecture.as_ref(), self.sibling_hash.as_ref()) {
+            (None, None) => count == 1,
+            (Some(sub), Some(&Side::Left(_))) if count > 1 => sub.fits(count - left_count),
+            (Some(sub), Some(&Side::Right(_))) if count > 1 => sub.fits(left_count),
+            _ => false,
+        }
+    }
+
+    fn validate<H: TreeHasher>(&self, hasher: &H) -> bool {
+        match self.sub_conjecture {
+            None => self.sibling_hash.is_none(),
+            Some(ref sub) => match self.sibling_hash {
+                None => false,
+                Some(Side::Left(ref hash)) => {
+                    let combined = hasher.node_hash(hash, &sub.node_hash);
+                    let hashes_match = digests_match(&combined, &self.node_hash);
+                    hashes_match && sub.validate(hasher)
+                }
+                Some(Side::Right(ref hash)) => {
+                    let combined = hasher.node_hash(&sub.node_hash, hash);
+                    let hashes_match = digests_match(&combined, &self.node_hash);
+                    hashes_match && sub.validate(hasher)
+                }
+            },
+        }
+    }
+}
+
+/// Compares two digests in constant time with respect to their contents.
+/// Digests of different lengths never match.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn digests_match(a: &[u8], b: &[u8]) -> bool {
+    constant_time::verify_slices_are_equal(a, b).is_ok()
+}
+
+/// Compares two digests in constant time with respect to their contents.
+/// Digests of different lengths never match.
+///
+/// Ring's comparison is backed by C code that is not built for `wasm32`,
+/// so the bytes are folded together here instead.
+#[cfg(target_arch = "wasm32")]
+pub fn digests_match(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    core::hint::black_box(diff) == 0
+}
+
+/// Tags a value so that we know from which branch of a `Tree` (if any) it was found.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Side<T> {
+    /// The value was found in the left branch
+    Left(T),
+    /// The value was found in the right branch
+    Right(T),
+}
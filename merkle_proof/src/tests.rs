@@ -0,0 +1,265 @@
+#![cfg(test)]
+
+extern crate serde_json;
+
+use crate::hashing::{algorithm_by_name, hmac_algorithm, Hashing, TreeHasher};
+use crate::leaf::FileLeaf;
+use crate::proof::{digests_match, Conjecture, Proof, Side};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use ring::digest::{Algorithm, SHA512};
+
+static DIGEST: &Algorithm = &SHA512;
+
+/// Builds by hand the proof for the first of two leaves `a` and `b`.
+fn two_leaf_proof(a: &[u8], b: &[u8]) -> Proof<Vec<u8>> {
+    let left = DIGEST.hash_leaf(&a);
+    let right = DIGEST.hash_leaf(&b);
+    let root = DIGEST.hash_nodes(&left, &right);
+
+    let conjecture = Conjecture {
+        node_hash: root.as_ref().into(),
+        sibling_hash: Some(Side::Right(right.as_ref().into())),
+        sub_conjecture: Some(Box::new(Conjecture {
+            node_hash: left.as_ref().into(),
+            sibling_hash: None,
+            sub_conjecture: None,
+        })),
+    };
+
+    Proof::new(DIGEST, root.as_ref().into(), conjecture, a.into())
+}
+
+#[test]
+fn test_digests_match() {
+    let a = DIGEST.hash_leaf(&vec![1]);
+    let b = DIGEST.hash_leaf(&vec![2]);
+
+    assert!(digests_match(a.as_ref(), a.as_ref()));
+    assert!(!digests_match(a.as_ref(), b.as_ref()));
+    assert!(!digests_match(a.as_ref(), &a.as_ref()[1..]));
+    assert!(!digests_match(&[], a.as_ref()));
+}
+
+#[test]
+fn test_validate_proof() {
+    let proof = two_leaf_proof(b"left", b"right");
+    let root_hash = proof.root_hash.clone();
+
+    assert!(proof.validate(&root_hash));
+    assert!(proof.is_for_leaf(DIGEST.hash_leaf(&b"left").as_ref()));
+    assert_eq!(proof.index(2), 0);
+
+    let mut other_root = root_hash.clone();
+    other_root[0] ^= 1;
+    assert!(!proof.validate(&other_root));
+
+    let mut tampered = proof;
+    tampered.conjecture.sub_conjecture = None;
+    assert!(!tampered.validate(&root_hash));
+}
+
+fn file_leaf() -> FileLeaf {
+    FileLeaf {
+        path: "dir/file.txt".into(),
+        size: 5,
+        mtime: Some(1_700_000_000),
+        mode: Some(0o644),
+        content_hash: ring::digest::digest(DIGEST, b"hello").as_ref().into(),
+    }
+}
+
+#[test]
+fn test_file_leaf_hash_commits_to_metadata() {
+    let leaf = file_leaf();
+    let hash = DIGEST.hash_leaf(&leaf);
+
+    let mut encoded = vec![0x00];
+    encoded.extend(leaf.encode());
+    assert_eq!(
+        hash.as_ref(),
+        ring::digest::digest(DIGEST, &encoded).as_ref()
+    );
+
+    let variants = [
+        FileLeaf {
+            path: "dir/file.txT".into(),
+            ..file_leaf()
+        },
+        FileLeaf {
+            size: 6,
+            ..file_leaf()
+        },
+        FileLeaf {
+            mtime: None,
+            ..file_leaf()
+        },
+        FileLeaf {
+            mode: Some(0o755),
+            ..file_leaf()
+        },
+        FileLeaf {
+            content_hash: ring::digest::digest(DIGEST, b"hellO").as_ref().into(),
+            ..file_leaf()
+        },
+    ];
+    for variant in &variants {
+        assert_ne!(DIGEST.hash_leaf(variant).as_ref(), hash.as_ref());
+    }
+
+    // The length prefix keeps the path from bleeding into the following fields.
+    let shifted = FileLeaf {
+        path: "a".into(),
+        content_hash: b"b".to_vec(),
+        ..file_leaf()
+    };
+    let unshifted = FileLeaf {
+        path: "ab".into(),
+        content_hash: Vec::new(),
+        ..file_leaf()
+    };
+    assert_ne!(shifted.encode(), unshifted.encode());
+}
+
+#[test]
+fn test_file_leaf_matches_content() {
+    let leaf = file_leaf();
+
+    assert!(leaf.matches_content(DIGEST, b"hello"));
+    assert!(!leaf.matches_content(DIGEST, b"hellO"));
+    assert!(!leaf.matches_content(DIGEST, b"hello!"));
+    assert!(!leaf.matches_content(algorithm_by_name("SHA256").unwrap(), b"hello"));
+    assert!(!FileLeaf {
+        size: 6,
+        ..file_leaf()
+    }
+    .matches_content(DIGEST, b"hello"));
+}
+
+#[test]
+fn test_file_leaf_is_safe_path() {
+    for path in ["file.txt", "dir/file.txt", "..file", "a/.b/c"] {
+        assert!(FileLeaf::is_safe_path(path), "{}", path);
+    }
+    for path in [
+        "",
+        "/etc/passwd",
+        "dir/",
+        "../file",
+        "dir/../file",
+        "./file",
+        "a//b",
+        "a\\b",
+    ] {
+        assert!(!FileLeaf::is_safe_path(path), "{}", path);
+    }
+}
+
+#[test]
+fn test_file_leaf_canonical_order() {
+    let leaf = |path: &str| FileLeaf {
+        path: path.into(),
+        ..file_leaf()
+    };
+    let mut leaves: Vec<_> = ["b", "a.txt", "\u{e9}", "B", "a", "z"]
+        .iter()
+        .map(|path| leaf(path))
+        .collect();
+    assert!(!FileLeaf::is_canonical_order(&leaves));
+
+    leaves.sort_by(FileLeaf::cmp_canonical);
+    let paths: Vec<_> = leaves.iter().map(|leaf| leaf.path.as_str()).collect();
+    assert_eq!(paths, ["B", "a", "a.txt", "b", "z", "\u{e9}"]);
+    assert!(FileLeaf::is_canonical_order(&leaves));
+    assert!(FileLeaf::is_canonical_order(&[]));
+
+    leaves.insert(1, leaf("a"));
+    assert!(!FileLeaf::is_canonical_order(&leaves));
+}
+
+#[test]
+fn test_proof_is_for_value() {
+    let leaf = file_leaf();
+    let leaf_hash = DIGEST.hash_leaf(&leaf);
+    let conjecture = Conjecture {
+        node_hash: leaf_hash.as_ref().into(),
+        sibling_hash: None,
+        sub_conjecture: None,
+    };
+    let mut proof = Proof::new(DIGEST, leaf_hash.as_ref().into(), conjecture, leaf);
+
+    assert!(proof.validate(leaf_hash.as_ref()));
+    assert!(proof.is_for_value());
+
+    proof.value.path = "other.txt".into();
+    assert!(!proof.is_for_value());
+}
+
+#[test]
+fn test_algorithm_by_name() {
+    for name in ["SHA1", "SHA256", "SHA384", "SHA512", "SHA512_256"] {
+        let algorithm = algorithm_by_name(name).expect("known algorithm");
+        assert_eq!(alloc::format!("{:?}", algorithm), name);
+    }
+    assert!(algorithm_by_name("MD5").is_none());
+}
+
+#[test]
+fn test_hmac_algorithm() {
+    for name in ["SHA1", "SHA256", "SHA384", "SHA512"] {
+        let algorithm = algorithm_by_name(name).expect("known algorithm");
+        let hmac = hmac_algorithm(algorithm).expect("HMAC algorithm");
+        assert!(core::ptr::eq(hmac.digest_algorithm(), algorithm));
+
+        let key = ring::hmac::Key::new(hmac, b"secret");
+        assert!(core::ptr::eq(TreeHasher::algorithm(&key), algorithm));
+        assert_eq!(key.leaf_hash(&b"leaf").len(), algorithm.output_len);
+        assert_ne!(key.leaf_hash(&b"leaf"), (&algorithm).leaf_hash(&b"leaf"));
+    }
+    assert!(hmac_algorithm(&ring::digest::SHA512_256).is_none());
+}
+
+#[test]
+fn test_serialize_proof_with_serde() {
+    let proof = two_leaf_proof(b"left", b"right");
+
+    let serialized = serde_json::to_string(&proof).expect("serialize proof");
+
+    assert_eq!(
+        proof,
+        serde_json::from_str(&serialized).expect("deserialize proof")
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_hash_leaf_reader() {
+    use crate::hashing::HashingReader;
+
+    let content = (0..100_000).map(|x| x as u8).collect::<Vec<_>>();
+    let expected = DIGEST.hash_leaf(&content);
+
+    let streamed = DIGEST
+        .hash_leaf_reader(content.as_slice())
+        .expect("hash from reader");
+    assert_eq!(streamed.as_ref(), expected.as_ref());
+
+    let mut copy = Vec::new();
+    let mut reader = HashingReader::new(content.as_slice(), DIGEST.leaf_context());
+    std::io::copy(&mut reader, &mut copy).expect("copy through hashing reader");
+    assert_eq!(copy, content);
+    assert_eq!(reader.finish().as_ref(), expected.as_ref());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_hash_leaf_async_reader() {
+    let content = (0..100_000).map(|x| x as u8).collect::<Vec<_>>();
+    let expected = DIGEST.hash_leaf(&content);
+
+    let streamed = crate::hashing::hash_leaf_async_reader(DIGEST, content.as_slice())
+        .await
+        .expect("hash from async reader");
+    assert_eq!(streamed.as_ref(), expected.as_ref());
+}
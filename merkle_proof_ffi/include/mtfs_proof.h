@@ -0,0 +1,146 @@
+#ifndef MTFS_PROOF_H
+#define MTFS_PROOF_H
+
+/* Generated with cbindgen from merkle_proof_ffi/src/lib.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The length of the longest digest produced by the supported algorithms.
+ */
+#define MTFS_MAX_DIGEST_LEN 64
+
+/**
+ * An inclusion proof parsed with `mtfs_proof_parse`.
+ */
+typedef struct MtfsProof MtfsProof;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Parses a JSON serialized proof, as sent by the server in the `proof` header,
+ * whose value is the leaf of a file.
+ *
+ * Returns `NULL` if the proof cannot be parsed.
+ * The returned proof must be released with `mtfs_proof_free`.
+ *
+ * # Safety
+ *
+ * `json` must point to `json_len` readable bytes.
+ */
+struct MtfsProof *mtfs_proof_parse(const uint8_t *json, size_t json_len);
+
+/**
+ * Releases a proof returned by `mtfs_proof_parse`. Does nothing on `NULL`.
+ *
+ * # Safety
+ *
+ * `proof` must be `NULL` or a pointer returned by `mtfs_proof_parse`
+ * that was not released yet.
+ */
+void mtfs_proof_free(struct MtfsProof *proof);
+
+/**
+ * Checks whether `proof` is well-formed, is about the leaf of its file,
+ * and leads to the root hash `root`. Proofs of keyed batches never verify.
+ *
+ * Returns `false` if any pointer is `NULL`.
+ *
+ * # Safety
+ *
+ * `proof` must be a live pointer returned by `mtfs_proof_parse`,
+ * and `root` must point to `root_len` readable bytes.
+ */
+bool mtfs_proof_verify(const struct MtfsProof *proof, const uint8_t *root, size_t root_len);
+
+/**
+ * Copies the hash of the leaf `proof` is about into `out`.
+ *
+ * Returns the length of the hash, or `0` if a pointer is `NULL`
+ * or `out_len` is too small.
+ *
+ * # Safety
+ *
+ * `proof` must be a live pointer returned by `mtfs_proof_parse`,
+ * and `out` must point to `out_len` writable bytes.
+ */
+size_t mtfs_proof_leaf_hash(const struct MtfsProof *proof, uint8_t *out, size_t out_len);
+
+/**
+ * Copies the digest of the content of the file `proof` is about into `out`.
+ *
+ * Returns the length of the digest, or `0` if a pointer is `NULL`
+ * or `out_len` is too small.
+ *
+ * # Safety
+ *
+ * `proof` must be a live pointer returned by `mtfs_proof_parse`,
+ * and `out` must point to `out_len` writable bytes.
+ */
+size_t mtfs_proof_content_hash(const struct MtfsProof *proof, uint8_t *out, size_t out_len);
+
+/**
+ * Returns the size in bytes of the file `proof` is about, or `0` if `proof` is `NULL`.
+ *
+ * # Safety
+ *
+ * `proof` must be `NULL` or a live pointer returned by `mtfs_proof_parse`.
+ */
+uint64_t mtfs_proof_file_size(const struct MtfsProof *proof);
+
+/**
+ * Copies the path of the file `proof` is about into `out`, NUL-terminated.
+ *
+ * Returns the length of the path without the NUL, or `0` if a pointer is `NULL`
+ * or `out_len` is too small.
+ *
+ * # Safety
+ *
+ * `proof` must be a live pointer returned by `mtfs_proof_parse`,
+ * and `out` must point to `out_len` writable bytes.
+ */
+size_t mtfs_proof_file_path(const struct MtfsProof *proof, char *out, size_t out_len);
+
+/**
+ * Checks whether the `len` bytes at `buf` have the size and the content digest
+ * of the file `proof` is about. Only meaningful if `mtfs_proof_verify` succeeds.
+ *
+ * Returns `false` if a pointer is `NULL`.
+ *
+ * # Safety
+ *
+ * `proof` must be a live pointer returned by `mtfs_proof_parse`,
+ * and `buf` must point to `len` readable bytes.
+ */
+bool mtfs_proof_content_matches(const struct MtfsProof *proof, const uint8_t *buf, size_t len);
+
+/**
+ * Computes the digest of the `len` bytes at `buf` with the algorithm named
+ * `algorithm`, e.g. `"SHA512"`, and writes it into `out`.
+ * This is the content digest of a file, see `mtfs_proof_content_hash`.
+ *
+ * Returns the length of the digest, or `0` if the algorithm is unknown,
+ * a pointer is `NULL` or `out_len` is too small.
+ *
+ * # Safety
+ *
+ * `algorithm` must be a NUL-terminated string,
+ * `buf` must point to `len` readable bytes and `out` to `out_len` writable bytes.
+ */
+size_t mtfs_hash_content(const char *algorithm,
+                         const uint8_t *buf,
+                         size_t len,
+                         uint8_t *out,
+                         size_t out_len);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* MTFS_PROOF_H */
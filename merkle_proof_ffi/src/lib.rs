@@ -0,0 +1,219 @@
+//! C API for verifying MTFS inclusion proofs.
+//!
+//! The header `include/mtfs_proof.h` is generated from this file with
+//! `cbindgen --config cbindgen.toml --output include/mtfs_proof.h`.
+
+use merkle_proof::{hashing::algorithm_by_name, leaf::FileLeaf, proof::Proof};
+use ring::digest::digest;
+use std::{ffi::CStr, os::raw::c_char, slice};
+
+/// The length of the longest digest produced by the supported algorithms.
+pub const MTFS_MAX_DIGEST_LEN: usize = 64;
+
+/// An inclusion proof parsed with `mtfs_proof_parse`.
+pub struct MtfsProof(Proof<FileLeaf>);
+
+/// Parses a JSON serialized proof, as sent by the server in the `proof` header,
+/// whose value is the leaf of a file.
+///
+/// Returns `NULL` if the proof cannot be parsed.
+/// The returned proof must be released with `mtfs_proof_free`.
+///
+/// # Safety
+///
+/// `json` must point to `json_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mtfs_proof_parse(json: *const u8, json_len: usize) -> *mut MtfsProof {
+    if json.is_null() {
+        return std::ptr::null_mut();
+    }
+    let json = slice::from_raw_parts(json, json_len);
+
+    match serde_json::from_slice(json) {
+        Ok(proof) => Box::into_raw(Box::new(MtfsProof(proof))),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Releases a proof returned by `mtfs_proof_parse`. Does nothing on `NULL`.
+///
+/// # Safety
+///
+/// `proof` must be `NULL` or a pointer returned by `mtfs_proof_parse`
+/// that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn mtfs_proof_free(proof: *mut MtfsProof) {
+    if !proof.is_null() {
+        drop(Box::from_raw(proof));
+    }
+}
+
+/// Checks whether `proof` is well-formed, is about the leaf of its file,
+/// and leads to the root hash `root`. Proofs of keyed batches never verify.
+///
+/// Returns `false` if any pointer is `NULL`.
+///
+/// # Safety
+///
+/// `proof` must be a live pointer returned by `mtfs_proof_parse`,
+/// and `root` must point to `root_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mtfs_proof_verify(
+    proof: *const MtfsProof,
+    root: *const u8,
+    root_len: usize,
+) -> bool {
+    if proof.is_null() || root.is_null() {
+        return false;
+    }
+    let proof = &(*proof).0;
+    proof.validate(slice::from_raw_parts(root, root_len)) && proof.is_for_value()
+}
+
+/// Copies the hash of the leaf `proof` is about into `out`.
+///
+/// Returns the length of the hash, or `0` if a pointer is `NULL`
+/// or `out_len` is too small.
+///
+/// # Safety
+///
+/// `proof` must be a live pointer returned by `mtfs_proof_parse`,
+/// and `out` must point to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mtfs_proof_leaf_hash(
+    proof: *const MtfsProof,
+    out: *mut u8,
+    out_len: usize,
+) -> usize {
+    if proof.is_null() {
+        return 0;
+    }
+    copy_digest((*proof).0.leaf_hash(), out, out_len)
+}
+
+/// Copies the digest of the content of the file `proof` is about into `out`.
+///
+/// Returns the length of the digest, or `0` if a pointer is `NULL`
+/// or `out_len` is too small.
+///
+/// # Safety
+///
+/// `proof` must be a live pointer returned by `mtfs_proof_parse`,
+/// and `out` must point to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mtfs_proof_content_hash(
+    proof: *const MtfsProof,
+    out: *mut u8,
+    out_len: usize,
+) -> usize {
+    if proof.is_null() {
+        return 0;
+    }
+    copy_digest(&(*proof).0.value.content_hash, out, out_len)
+}
+
+/// Returns the size in bytes of the file `proof` is about, or `0` if `proof` is `NULL`.
+///
+/// # Safety
+///
+/// `proof` must be `NULL` or a live pointer returned by `mtfs_proof_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn mtfs_proof_file_size(proof: *const MtfsProof) -> u64 {
+    if proof.is_null() {
+        return 0;
+    }
+    (*proof).0.value.size
+}
+
+/// Copies the path of the file `proof` is about into `out`, NUL-terminated.
+///
+/// Returns the length of the path without the NUL, or `0` if a pointer is `NULL`
+/// or `out_len` is too small.
+///
+/// # Safety
+///
+/// `proof` must be a live pointer returned by `mtfs_proof_parse`,
+/// and `out` must point to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mtfs_proof_file_path(
+    proof: *const MtfsProof,
+    out: *mut c_char,
+    out_len: usize,
+) -> usize {
+    if proof.is_null() || out.is_null() {
+        return 0;
+    }
+    let path = (*proof).0.value.path.as_bytes();
+    if out_len <= path.len() {
+        return 0;
+    }
+    std::ptr::copy_nonoverlapping(path.as_ptr(), out as *mut u8, path.len());
+    *out.add(path.len()) = 0;
+    path.len()
+}
+
+/// Checks whether the `len` bytes at `buf` have the size and the content digest
+/// of the file `proof` is about. Only meaningful if `mtfs_proof_verify` succeeds.
+///
+/// Returns `false` if a pointer is `NULL`.
+///
+/// # Safety
+///
+/// `proof` must be a live pointer returned by `mtfs_proof_parse`,
+/// and `buf` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mtfs_proof_content_matches(
+    proof: *const MtfsProof,
+    buf: *const u8,
+    len: usize,
+) -> bool {
+    if proof.is_null() || buf.is_null() {
+        return false;
+    }
+    let proof = &(*proof).0;
+    proof
+        .value
+        .matches_content(proof.algorithm, slice::from_raw_parts(buf, len))
+}
+
+/// Computes the digest of the `len` bytes at `buf` with the algorithm named
+/// `algorithm`, e.g. `"SHA512"`, and writes it into `out`.
+/// This is the content digest of a file, see `mtfs_proof_content_hash`.
+///
+/// Returns the length of the digest, or `0` if the algorithm is unknown,
+/// a pointer is `NULL` or `out_len` is too small.
+///
+/// # Safety
+///
+/// `algorithm` must be a NUL-terminated string,
+/// `buf` must point to `len` readable bytes and `out` to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn mtfs_hash_content(
+    algorithm: *const c_char,
+    buf: *const u8,
+    len: usize,
+    out: *mut u8,
+    out_len: usize,
+) -> usize {
+    if algorithm.is_null() || buf.is_null() {
+        return 0;
+    }
+    let algorithm = match CStr::from_ptr(algorithm)
+        .to_str()
+        .ok()
+        .and_then(algorithm_by_name)
+    {
+        Some(algorithm) => algorithm,
+        None => return 0,
+    };
+    let content_hash = digest(algorithm, slice::from_raw_parts(buf, len));
+    copy_digest(content_hash.as_ref(), out, out_len)
+}
+
+unsafe fn copy_digest(digest: &[u8], out: *mut u8, out_len: usize) -> usize {
+    if out.is_null() || out_len < digest.len() {
+        return 0;
+    }
+    std::ptr::copy_nonoverlapping(digest.as_ptr(), out, digest.len());
+    digest.len()
+}
@@ -0,0 +1,95 @@
+//! Builds `tests/verify_proof.c` against the static library and runs it
+//! on proofs generated by `merkle_tree`.
+
+use merkle_tree::{hashing::Profile, leaf::FileLeaf, merkletree::MerkleTree, proof::Side};
+use ring::digest::{digest, SHA512};
+use std::{fs, path::PathBuf, process::Command};
+
+#[test]
+fn c_program_verifies_proofs() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // The test binary lives in `target/<profile>/deps`, next to which cargo puts the library.
+    let profile_dir = std::env::current_exe()
+        .expect("test executable path")
+        .parent()
+        .and_then(|deps| deps.parent())
+        .expect("target profile directory")
+        .to_path_buf();
+    let work_dir = profile_dir.join("c_api_test");
+    fs::create_dir_all(&work_dir).expect("create work directory");
+
+    // A batch of files, hashed as the server does.
+    let contents = (0..5u8).map(|i| vec![i; 100]).collect::<Vec<_>>();
+    let leaves = contents
+        .iter()
+        .enumerate()
+        .map(|(i, content)| FileLeaf {
+            path: format!("file{}.bin", i),
+            size: content.len() as u64,
+            mtime: Some(1_700_000_000),
+            mode: Some(0o644),
+            content_hash: digest(&SHA512, content).as_ref().to_vec(),
+        })
+        .collect();
+    let tree = MerkleTree::from_vec_with_profile(&SHA512, Profile::Hardened, leaves);
+    let proof = tree.gen_nth_proof(3).expect("gen proof by index");
+
+    let mut tampered = proof.clone();
+    match tampered.conjecture.sibling_hash {
+        Some(Side::Left(ref mut hash)) | Some(Side::Right(ref mut hash)) => hash[0] ^= 1,
+        None => unreachable!("a tree of five leaves has siblings"),
+    }
+
+    let proof_path = work_dir.join("proof.json");
+    let forged_path = work_dir.join("forged.json");
+    let tampered_path = work_dir.join("tampered.json");
+    let root_path = work_dir.join("root.bin");
+    let content_path = work_dir.join("content.bin");
+    fs::write(&proof_path, serde_json::to_vec(&proof).unwrap()).unwrap();
+    // A proof for other content, whose path still leads to the root.
+    let mut forged = proof;
+    forged.value.content_hash = digest(&SHA512, b"other").as_ref().to_vec();
+    forged.value.size = 5;
+    fs::write(&tampered_path, serde_json::to_vec(&tampered).unwrap()).unwrap();
+    fs::write(&forged_path, serde_json::to_vec(&forged).unwrap()).unwrap();
+    fs::write(&root_path, tree.root_hash()).unwrap();
+    fs::write(&content_path, &contents[3]).unwrap();
+
+    // Integration tests only get the rlib, so build the static library explicitly.
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut build = Command::new(cargo);
+    build.args(["build", "--lib", "-p", "merkle_proof_ffi"]);
+    if !cfg!(debug_assertions) {
+        build.arg("--release");
+    }
+    let status = build.status().expect("run cargo");
+    assert!(status.success(), "building the static library failed");
+
+    let program = work_dir.join("verify_proof");
+    let status = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg(manifest_dir.join("tests").join("verify_proof.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(profile_dir.join("libmerkle_proof_ffi.a"))
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&program)
+        .status()
+        .expect("run the C compiler");
+    assert!(status.success(), "compiling the C test program failed");
+
+    let output = Command::new(&program)
+        .args([
+            &proof_path,
+            &tampered_path,
+            &forged_path,
+            &root_path,
+            &content_path,
+        ])
+        .output()
+        .expect("run the C test program");
+    assert!(
+        output.status.success(),
+        "C test program failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,117 @@
+/*
+ * Exercises the C API against a valid, a tampered and a forged proof.
+ *
+ * Usage: verify_proof <proof.json> <tampered.json> <forged.json> <root.bin>
+ *                     <content.bin>
+ * where the proof is for the file `file3.bin`, whose content is `content.bin`,
+ * in a batch whose root hash is `root.bin`, and the forged proof claims other
+ * content for the same leaf.
+ */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "mtfs_proof.h"
+
+#define CHECK(cond)                                                \
+    do {                                                           \
+        if (!(cond)) {                                             \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, \
+                    __LINE__, #cond);                              \
+            return 1;                                              \
+        }                                                          \
+    } while (0)
+
+static uint8_t *read_file(const char *path, size_t *len) {
+    FILE *file = fopen(path, "rb");
+    if (file == NULL) {
+        return NULL;
+    }
+    fseek(file, 0, SEEK_END);
+    *len = (size_t)ftell(file);
+    fseek(file, 0, SEEK_SET);
+    uint8_t *buf = malloc(*len + 1);
+    if (buf != NULL && fread(buf, 1, *len, file) != *len) {
+        free(buf);
+        buf = NULL;
+    }
+    fclose(file);
+    return buf;
+}
+
+int main(int argc, char **argv) {
+    size_t proof_len, tampered_len, forged_len, root_len, content_len;
+    uint8_t expected[MTFS_MAX_DIGEST_LEN], computed[MTFS_MAX_DIGEST_LEN];
+    char path[16];
+
+    CHECK(argc == 6);
+    uint8_t *proof_json = read_file(argv[1], &proof_len);
+    uint8_t *tampered_json = read_file(argv[2], &tampered_len);
+    uint8_t *forged_json = read_file(argv[3], &forged_len);
+    uint8_t *root = read_file(argv[4], &root_len);
+    uint8_t *content = read_file(argv[5], &content_len);
+    CHECK(proof_json && tampered_json && forged_json && root && content);
+
+    /* A valid proof verifies, and is about the given file. */
+    MtfsProof *proof = mtfs_proof_parse(proof_json, proof_len);
+    CHECK(proof != NULL);
+    CHECK(mtfs_proof_verify(proof, root, root_len));
+    CHECK(mtfs_proof_leaf_hash(proof, computed, sizeof(computed)) == 64);
+
+    CHECK(mtfs_proof_file_path(proof, path, sizeof(path)) == 9);
+    CHECK(strcmp(path, "file3.bin") == 0);
+    CHECK(mtfs_proof_file_path(proof, path, 9) == 0);
+    CHECK(mtfs_proof_file_size(proof) == content_len);
+
+    CHECK(mtfs_proof_content_matches(proof, content, content_len));
+    size_t hash_len = mtfs_proof_content_hash(proof, expected, sizeof(expected));
+    CHECK(hash_len == 64);
+    CHECK(mtfs_hash_content("SHA512", content, content_len, computed,
+                            sizeof(computed)) == hash_len);
+    CHECK(memcmp(expected, computed, hash_len) == 0);
+
+    /* Tampered or truncated content no longer matches the file. */
+    CHECK(!mtfs_proof_content_matches(proof, content, content_len - 1));
+    content[0] ^= 1;
+    CHECK(!mtfs_proof_content_matches(proof, content, content_len));
+    CHECK(mtfs_hash_content("SHA512", content, content_len, computed,
+                            sizeof(computed)) == hash_len);
+    CHECK(memcmp(expected, computed, hash_len) != 0);
+
+    /* A different root is rejected. */
+    root[root_len - 1] ^= 1;
+    CHECK(!mtfs_proof_verify(proof, root, root_len));
+    root[root_len - 1] ^= 1;
+    CHECK(!mtfs_proof_verify(proof, root, root_len - 1));
+    mtfs_proof_free(proof);
+
+    /* A tampered proof parses but does not verify. */
+    MtfsProof *tampered = mtfs_proof_parse(tampered_json, tampered_len);
+    CHECK(tampered != NULL);
+    CHECK(!mtfs_proof_verify(tampered, root, root_len));
+    mtfs_proof_free(tampered);
+
+    /* A proof whose file leaf is not the proven leaf does not verify. */
+    MtfsProof *forged = mtfs_proof_parse(forged_json, forged_len);
+    CHECK(forged != NULL);
+    CHECK(!mtfs_proof_verify(forged, root, root_len));
+    mtfs_proof_free(forged);
+
+    /* Malformed input is rejected. */
+    CHECK(mtfs_proof_parse((const uint8_t *)"{}", 2) == NULL);
+    CHECK(mtfs_proof_parse(NULL, 0) == NULL);
+    CHECK(!mtfs_proof_verify(NULL, root, root_len));
+    CHECK(!mtfs_proof_content_matches(NULL, content, content_len));
+    CHECK(mtfs_hash_content("MD5", content, content_len, computed,
+                            sizeof(computed)) == 0);
+    CHECK(mtfs_hash_content("SHA512", content, content_len, computed, 1) == 0);
+    mtfs_proof_free(NULL);
+
+    free(proof_json);
+    free(tampered_json);
+    free(forged_json);
+    free(root);
+    free(content);
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,78 @@
+use merkle_proof::{hashing::algorithm_by_name, leaf::FileLeaf, proof::Proof};
+use ring::digest::digest;
+use wasm_bindgen::prelude::*;
+
+/// The outcome of verifying a proof against a root hash.
+#[wasm_bindgen]
+pub struct Verification {
+    valid: bool,
+    proof: Proof<FileLeaf>,
+}
+
+#[wasm_bindgen]
+impl Verification {
+    /// Whether the proof is well-formed, is about its file leaf, and leads to the given
+    /// root hash. Proofs of keyed batches cannot be verified without their key.
+    #[wasm_bindgen(getter)]
+    pub fn valid(&self) -> bool {
+        self.valid
+    }
+
+    /// The hash of the leaf the proof is about.
+    #[wasm_bindgen(getter, js_name = leafHash)]
+    pub fn leaf_hash(&self) -> Vec<u8> {
+        self.proof.leaf_hash().to_vec()
+    }
+
+    /// The path of the file, relative to its batch.
+    #[wasm_bindgen(getter)]
+    pub fn path(&self) -> String {
+        self.proof.value.path.clone()
+    }
+
+    /// The size of the file, in bytes.
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> u64 {
+        self.proof.value.size
+    }
+
+    /// The digest of the content of the file.
+    /// Compare it with `hashContent` of the downloaded content, or use `matchesContent`.
+    #[wasm_bindgen(getter, js_name = contentHash)]
+    pub fn content_hash(&self) -> Vec<u8> {
+        self.proof.value.content_hash.clone()
+    }
+
+    /// Whether `content` has the size and the digest of the file.
+    /// Only meaningful if the proof is `valid`.
+    #[wasm_bindgen(js_name = matchesContent)]
+    pub fn matches_content(&self, content: &[u8]) -> bool {
+        self.proof
+            .value
+            .matches_content(self.proof.algorithm, content)
+    }
+}
+
+/// Verifies a JSON serialized proof, as sent by the server in the `proof` header,
+/// against `root_hash`.
+/// Throws if the proof cannot be parsed.
+#[wasm_bindgen]
+pub fn verify(proof: &str, root_hash: &[u8]) -> Result<Verification, JsError> {
+    let proof: Proof<FileLeaf> = serde_json::from_str(proof)?;
+
+    Ok(Verification {
+        valid: proof.validate(root_hash) && proof.is_for_value(),
+        proof,
+    })
+}
+
+/// Computes the digest of `content` with the algorithm named `algorithm`, e.g. `"SHA512"`,
+/// as found in the `contentHash` of a file.
+/// Throws if the algorithm is unknown.
+#[wasm_bindgen(js_name = hashContent)]
+pub fn hash_content(algorithm: &str, content: &[u8]) -> Result<Vec<u8>, JsError> {
+    let algorithm =
+        algorithm_by_name(algorithm).ok_or_else(|| JsError::new("unknown hash algorithm"))?;
+
+    Ok(digest(algorithm, content).as_ref().to_vec())
+}
@@ -0,0 +1,89 @@
+//! Run with `wasm-pack test --node merkle_proof_wasm`, or with
+//! `cargo test --target wasm32-unknown-unknown -p merkle_proof_wasm`
+//! when `wasm-bindgen-test-runner` is set as the cargo runner.
+#![cfg(target_arch = "wasm32")]
+
+use merkle_proof_wasm::{hash_content, verify};
+use merkle_tree::{
+    hashing::Profile,
+    leaf::FileLeaf,
+    merkletree::MerkleTree,
+    proof::{Proof, Side},
+};
+use ring::digest::{digest, SHA512};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const CONTENTS: [&[u8]; 3] = [b"first", b"second", b"third"];
+
+/// Builds the proof for the second file of a batch, as the server sends it,
+/// and the root hash of the batch.
+fn proof_and_root() -> (Proof<FileLeaf>, Vec<u8>) {
+    let leaves = ["a", "b", "c"]
+        .iter()
+        .zip(CONTENTS)
+        .map(|(path, content)| FileLeaf {
+            path: path.to_string(),
+            size: content.len() as u64,
+            mtime: Some(1_700_000_000),
+            mode: Some(0o644),
+            content_hash: digest(&SHA512, content).as_ref().to_vec(),
+        })
+        .collect();
+    let tree = MerkleTree::from_vec_with_profile(&SHA512, Profile::Hardened, leaves);
+
+    (tree.gen_nth_proof(1).unwrap(), tree.root_hash().clone())
+}
+
+#[wasm_bindgen_test]
+fn valid_proof() {
+    let (proof, root) = proof_and_root();
+    let proof = serde_json::to_string(&proof).unwrap();
+
+    let verification = verify(&proof, &root).ok().expect("parse proof");
+    assert!(verification.valid());
+    assert_eq!(verification.path(), "b");
+    assert_eq!(verification.size(), 6);
+    assert!(verification.matches_content(CONTENTS[1]));
+    assert!(!verification.matches_content(b"secont"));
+    assert!(!verification.matches_content(CONTENTS[2]));
+    assert_eq!(
+        verification.content_hash(),
+        hash_content("SHA512", CONTENTS[1])
+            .ok()
+            .expect("hash content")
+    );
+}
+
+#[wasm_bindgen_test]
+fn wrong_root() {
+    let (proof, mut root) = proof_and_root();
+    let proof = serde_json::to_string(&proof).unwrap();
+    root[0] ^= 1;
+
+    assert!(!verify(&proof, &root).ok().expect("parse proof").valid());
+}
+
+#[wasm_bindgen_test]
+fn tampered_sibling() {
+    let (mut proof, root) = proof_and_root();
+    proof.conjecture.sibling_hash = Some(Side::Right(vec![0; 64]));
+    let proof = serde_json::to_string(&proof).unwrap();
+
+    assert!(!verify(&proof, &root).ok().expect("parse proof").valid());
+}
+
+#[wasm_bindgen_test]
+fn tampered_value() {
+    let (mut proof, root) = proof_and_root();
+    proof.value.content_hash = digest(&SHA512, b"other").as_ref().to_vec();
+    let proof = serde_json::to_string(&proof).unwrap();
+
+    let verification = verify(&proof, &root).ok().expect("parse proof");
+    assert!(!verification.valid());
+}
+
+#[wasm_bindgen_test]
+fn malformed_input() {
+    assert!(verify("{}", &[]).is_err());
+    assert!(hash_content("MD5", b"a").is_err());
+}
@@ -0,0 +1,4 @@
+//! Consistency proofs, shared with the `merkle_proof` verifier crate.
+//! Proofs are generated by `MerkleTree::gen_consistency_proof`.
+
+pub use merkle_proof::consistency::*;
@@ -1,76 +1,3 @@
-use ring::digest::{digest, Algorithm, Context, Digest};
+//! Leaf and node hashing, shared with the `merkle_proof` verifier crate.
 
-/// The type of values stored in a `MerkleTree` must implement
-/// this trait, in order for them to be able to be fed
-/// to a Ring `Context` when computing the hash of a leaf.
-///
-/// A default instance for types that already implements
-/// `AsRef<[u8]>` is provided.
-///
-/// ## Example
-///
-/// Here is an example of how to implement `Hashable` for a type
-/// that does not (or cannot) implement `AsRef<[u8]>`:
-///
-/// ```ignore
-/// impl Hashable for PublicKey {
-///     fn update_context(&self, context: &mut Context) {
-///         let bytes: Vec<u8> = self.to_bytes();
-///         context.update(&bytes);
-///     }
-/// }
-/// ```
-pub trait Hashable {
-    /// Update the given `context` with `self`.
-    ///
-    /// See `ring::digest::Context::update` for more information.
-    fn update_context(&self, context: &mut Context);
-}
-
-impl<T: AsRef<[u8]>> Hashable for T {
-    fn update_context(&self, context: &mut Context) {
-        context.update(self.as_ref());
-    }
-}
-
-pub trait Hashing {
-    /// Compute the hash of the empty string
-    fn hash_empty(&'static self) -> Digest;
-
-    /// Compute the hash of the given leaf
-    fn hash_leaf<T>(&'static self, bytes: &T) -> Digest
-    where
-        T: Hashable;
-
-    /// Compute the hash of the concatenation of `left` and `right`.
-    fn hash_nodes<T>(&'static self, left: &T, right: &T) -> Digest
-    where
-        T: Hashable;
-}
-
-impl Hashing for Algorithm {
-    fn hash_empty(&'static self) -> Digest {
-        digest(self, &[])
-    }
-
-    fn hash_leaf<T>(&'static self, leaf: &T) -> Digest
-    where
-        T: Hashable,
-    {
-        let mut ctx = Context::new(self);
-        ctx.update(&[0x00]);
-        leaf.update_context(&mut ctx);
-        ctx.finish()
-    }
-
-    fn hash_nodes<T>(&'static self, left: &T, right: &T) -> Digest
-    where
-        T: Hashable,
-    {
-        let mut ctx = Context::new(self);
-        ctx.update(&[0x01]);
-        left.update_context(&mut ctx);
-        right.update_context(&mut ctx);
-        ctx.finish()
-    }
-}
+pub use merkle_proof::hashing::*;
@@ -0,0 +1,110 @@
+//! Signed tree heads: an Ed25519 attestation of the root of a batch,
+//! so that whoever shares a batch can check who produced its root.
+
+use crate::merkletree::MerkleTree;
+use crate::proof::{algorithm_serde, digests_match, Proof};
+use ring::digest::Algorithm;
+use ring::signature::{self, Ed25519KeyPair, KeyPair};
+use serde_derive::{Deserialize, Serialize};
+
+/// Prefix of the signed bytes, so that a head signature cannot be
+/// mistaken for a signature over anything else.
+const SIGNING_CONTEXT: &[u8] = b"MTFS tree head v1\0";
+
+/// The head of the tree of a batch: what a signer attests to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeHead {
+    /// The batch the tree is for
+    pub batch_id: String,
+    /// The hashing algorithm of the tree
+    #[serde(with = "algorithm_serde")]
+    pub algorithm: &'static Algorithm,
+    /// The number of leaves of the tree
+    pub size: u64,
+    /// The root hash of the tree
+    pub root_hash: Vec<u8>,
+    /// When the head was made, in seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// A `TreeHead` with the Ed25519 signature of its signer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTreeHead {
+    /// The signed head
+    pub head: TreeHead,
+    /// The Ed25519 public key of the signer
+    pub public_key: Vec<u8>,
+    /// The signature of `head.signing_bytes()`
+    pub signature: Vec<u8>,
+}
+
+impl TreeHead {
+    /// Returns the head of `tree`, for the batch `batch_id`.
+    pub fn from_tree<T>(batch_id: String, tree: &MerkleTree<T>, timestamp: u64) -> Self {
+        TreeHead {
+            batch_id,
+            algorithm: tree.algorithm,
+            size: tree.count() as u64,
+            root_hash: tree.root_hash().clone(),
+            timestamp,
+        }
+    }
+
+    /// Returns the bytes that are signed for this head.
+    ///
+    /// These are a fixed context string, then the batch id, the name of the algorithm,
+    /// the size, the root hash and the timestamp, each prefixed with its length in bytes.
+    /// Lengths and integers are big-endian `u64`s.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let algorithm = format!("{:?}", self.algorithm);
+        let mut bytes = SIGNING_CONTEXT.to_vec();
+        for field in [
+            self.batch_id.as_bytes(),
+            algorithm.as_bytes(),
+            &self.size.to_be_bytes(),
+            &self.root_hash,
+            &self.timestamp.to_be_bytes(),
+        ] {
+            bytes.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(field);
+        }
+        bytes
+    }
+
+    /// Signs this head with `key_pair`.
+    pub fn sign(self, key_pair: &Ed25519KeyPair) -> SignedTreeHead {
+        let signature = key_pair.sign(&self.signing_bytes());
+        SignedTreeHead {
+            head: self,
+            public_key: key_pair.public_key().as_ref().to_vec(),
+            signature: signature.as_ref().to_vec(),
+        }
+    }
+
+    /// Checks whether `proof` is for the tree of this head.
+    /// This does not validate the proof itself.
+    ///
+    /// Proofs that carry the number of leaves of their tree must match the size of the head.
+    pub fn is_for_proof<T>(&self, proof: &Proof<T>) -> bool {
+        std::ptr::eq(self.algorithm, proof.algorithm)
+            && digests_match(&self.root_hash, &proof.root_hash)
+            && proof.count.map_or(true, |count| count as u64 == self.size)
+    }
+}
+
+impl SignedTreeHead {
+    /// Checks whether the signature is valid for the head and the embedded public key.
+    ///
+    /// This only shows that the holder of `public_key` signed the head:
+    /// see `is_signed_by` to check the signer against a trusted key.
+    pub fn verify(&self) -> bool {
+        signature::UnparsedPublicKey::new(&signature::ED25519, &self.public_key)
+            .verify(&self.head.signing_bytes(), &self.signature)
+            .is_ok()
+    }
+
+    /// Checks whether the head was signed by the holder of the given Ed25519 public key.
+    pub fn is_signed_by(&self, public_key: &[u8]) -> bool {
+        digests_match(&self.public_key, public_key) && self.verify()
+    }
+}
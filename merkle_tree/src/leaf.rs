@@ -0,0 +1,3 @@
+//! Structured leaves, shared with the `merkle_proof` verifier crate.
+
+pub use merkle_proof::leaf::*;
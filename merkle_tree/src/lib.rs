@@ -1,7 +1,13 @@
-mod hashing;
+pub mod consistency;
+pub mod hashing;
+pub mod head;
+pub mod leaf;
+pub mod log;
 pub mod merkletree;
 pub mod proof;
+pub mod render;
 mod tree;
+pub mod visit;
 
 #[cfg(test)]
 mod tests;
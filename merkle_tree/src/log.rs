@@ -0,0 +1,330 @@
+//! An append-only log of the roots of batches, itself kept in a Merkle tree,
+//! so that a server can prove which root it accepted for a batch and that
+//! it never rewrote its history.
+
+use crate::consistency::ConsistencyProof;
+use crate::hashing::{Hashable, Hashing, Profile};
+use crate::proof::{algorithm_serde, digests_match, Conjecture, Proof, Side};
+use ring::digest::{Algorithm, Context};
+use serde_derive::{Deserialize, Serialize};
+
+/// An entry of the log: the root of a batch, as accepted by the server.
+///
+/// Each entry is chained to the one before it through `previous_hash`,
+/// and is hashed as its fields in order, with the same encoding as `FileLeaf`:
+/// strings and byte strings are prefixed with their length as a big-endian `u64`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// The batch the root is for
+    pub batch_id: String,
+    /// The root hash of the tree of the batch
+    pub root_hash: Vec<u8>,
+    /// The number of files of the batch
+    pub size: u64,
+    /// The leaf hash of the previous entry of the log, empty for the first one
+    pub previous_hash: Vec<u8>,
+}
+
+impl Hashable for LogEntry {
+    fn update_context(&self, context: &mut Context) {
+        context.update(&(self.batch_id.len() as u64).to_be_bytes());
+        context.update(self.batch_id.as_bytes());
+        context.update(&(self.root_hash.len() as u64).to_be_bytes());
+        context.update(&self.root_hash);
+        context.update(&self.size.to_be_bytes());
+        context.update(&(self.previous_hash.len() as u64).to_be_bytes());
+        context.update(&self.previous_hash);
+    }
+}
+
+/// The head of the log: the root of its tree for a given number of entries.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogHead {
+    /// The hashing algorithm of the log
+    #[serde(with = "algorithm_serde")]
+    pub algorithm: &'static Algorithm,
+    /// The hashing profile of the log
+    pub profile: Profile,
+    /// The number of entries of the log
+    pub size: u64,
+    /// The root hash of the tree of the first `size` entries
+    pub root_hash: Vec<u8>,
+}
+
+/// An append-only log of `LogEntry`s.
+///
+/// The log keeps the hash of every complete subtree of its tree, so that appending
+/// an entry takes O(log size) hashes, and the head or a proof O(log² size) at most.
+#[derive(Clone, Debug)]
+pub struct Log {
+    algorithm: &'static Algorithm,
+    profile: Profile,
+    entries: Vec<LogEntry>,
+    /// The hashes of the complete subtrees of `2^level` entries, by level:
+    /// `levels[level][i]` is the hash of the entries `i * 2^level..(i + 1) * 2^level`
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+impl Log {
+    /// Creates an empty log.
+    pub fn new(algorithm: &'static Algorithm, profile: Profile) -> Self {
+        Log {
+            algorithm,
+            profile,
+            entries: Vec::new(),
+            levels: Vec::new(),
+        }
+    }
+
+    /// Restores a log from its entries, checking that they are chained.
+    pub fn from_entries(
+        algorithm: &'static Algorithm,
+        profile: Profile,
+        entries: Vec<LogEntry>,
+    ) -> Result<Self, String> {
+        let mut log = Log::new(algorithm, profile);
+        for (index, entry) in entries.into_iter().enumerate() {
+            if !digests_match(&entry.previous_hash, &log.last_hash()) {
+                return Err(format!(
+                    "entry {} is not chained to the previous one",
+                    index
+                ));
+            }
+            log.push(entry);
+        }
+        Ok(log)
+    }
+
+    /// Appends the root of a batch to the log, and returns the index of the new entry.
+    pub fn append(&mut self, batch_id: String, root_hash: Vec<u8>, size: u64) -> usize {
+        let previous_hash = self.last_hash();
+        self.push(LogEntry {
+            batch_id,
+            root_hash,
+            size,
+            previous_hash,
+        });
+        self.entries.len() - 1
+    }
+
+    /// Removes the entries after the first `size` ones, e.g. to undo an append
+    /// that could not be saved.
+    pub fn truncate(&mut self, size: usize) {
+        self.entries.truncate(size);
+        for (level, hashes) in self.levels.iter_mut().enumerate() {
+            hashes.truncate(size >> level);
+        }
+    }
+
+    /// Returns the leaf hash of the last entry, or an empty hash if the log is empty.
+    fn last_hash(&self) -> Vec<u8> {
+        match (self.levels.first(), self.size().checked_sub(1)) {
+            (Some(leaves), Some(last)) => leaves[last].clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Adds `entry` to the log, along with the complete subtrees it completes.
+    fn push(&mut self, entry: LogEntry) {
+        let mut hash = self.algorithm.hash_leaf(&entry).as_ref().to_vec();
+        self.entries.push(entry);
+        let mut level = 0;
+        loop {
+            if self.levels.len() == level {
+                self.levels.push(Vec::new());
+            }
+            let hashes = &mut self.levels[level];
+            hashes.push(hash);
+            if hashes.len() % 2 == 1 {
+                break;
+            }
+            let (left, right) = (&hashes[hashes.len() - 2], &hashes[hashes.len() - 1]);
+            hash = self.algorithm.hash_nodes(left, right).as_ref().to_vec();
+            level += 1;
+        }
+    }
+
+    /// Returns the hash of the subtree of the `count` entries from `start`, which is
+    /// the leftmost subtree of `count` entries of a larger tree, or the whole tree.
+    fn subtree_hash(&self, start: usize, count: usize) -> Vec<u8> {
+        if count.is_power_of_two() {
+            let level = count.trailing_zeros() as usize;
+            return self.levels[level][start >> level].clone();
+        }
+        let left_count = count.next_power_of_two() / 2;
+        let left = self.subtree_hash(start, left_count);
+        let right = self.subtree_hash(start + left_count, count - left_count);
+        self.algorithm.hash_nodes(&left, &right).as_ref().to_vec()
+    }
+
+    /// Returns the root hash of the log as it was when it had `size` entries.
+    fn root_hash(&self, size: usize) -> Vec<u8> {
+        let top_hash = match size {
+            0 => self.profile.hash_empty(self.algorithm).as_ref().to_vec(),
+            _ => self.subtree_hash(0, size),
+        };
+        self.profile.root_hash(self.algorithm, size, &top_hash)
+    }
+
+    /// Returns the entries of the log, oldest first.
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    /// Returns the number of entries of the log.
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the current head of the log.
+    pub fn head(&self) -> LogHead {
+        LogHead {
+            algorithm: self.algorithm,
+            profile: self.profile,
+            size: self.size() as u64,
+            root_hash: self.root_hash(self.size()),
+        }
+    }
+
+    /// Returns the index of the latest entry for `batch_id`
+    /// among the first `size` entries of the log.
+    pub fn find(&self, batch_id: &str, size: usize) -> Option<usize> {
+        self.entries
+            .get(..size)?
+            .iter()
+            .rposition(|entry| entry.batch_id == batch_id)
+    }
+
+    /// Returns the proof that the entry at `index` is in the log
+    /// as it was when it had `size` entries.
+    pub fn inclusion_proof(&self, index: usize, size: usize) -> Option<Proof<LogEntry>> {
+        if index >= size || size > self.size() {
+            return None;
+        }
+        let conjecture = self.conjecture(index, 0, size);
+        let proof = Proof::new(
+            self.algorithm,
+            self.root_hash(size),
+            conjecture,
+            self.entries[index].clone(),
+        );
+        Some(proof.with_profile(self.profile, size))
+    }
+
+    /// Returns the path to the entry at `start + index` in the subtree of the `count`
+    /// entries from `start`, as `BinaryTree::conjecture_by_index` does.
+    fn conjecture(&self, index: usize, start: usize, count: usize) -> Conjecture {
+        let node_hash = self.subtree_hash(start, count);
+        if count == 1 {
+            return Conjecture {
+                node_hash,
+                sibling_hash: None,
+                sub_conjecture: None,
+            };
+        }
+        let left_count = count.next_power_of_two() / 2;
+        let (sub_conjecture, sibling_hash) = if index < left_count {
+            (
+                self.conjecture(index, start, left_count),
+                Side::Right(self.subtree_hash(start + left_count, count - left_count)),
+            )
+        } else {
+            (
+                self.conjecture(index - left_count, start + left_count, count - left_count),
+                Side::Left(self.subtree_hash(start, left_count)),
+            )
+        };
+        Conjecture {
+            node_hash,
+            sibling_hash: Some(sibling_hash),
+            sub_conjecture: Some(Box::new(sub_conjecture)),
+        }
+    }
+
+    /// Returns the proof that the log with `old_size` entries
+    /// is a prefix of the log with `new_size` entries.
+    pub fn consistency_proof(&self, old_size: usize, new_size: usize) -> Option<ConsistencyProof> {
+        if old_size > new_size || new_size > self.size() {
+            return None;
+        }
+        let mut hashes = Vec::new();
+        if 0 < old_size && old_size < new_size {
+            let keep_complete = self.profile == Profile::Hardened;
+            self.consistency_path(old_size, 0, new_size, true, keep_complete, &mut hashes);
+        }
+        Some(ConsistencyProof {
+            algorithm: self.algorithm,
+            profile: self.profile,
+            old_size,
+            new_size,
+            hashes,
+        })
+    }
+
+    /// Pushes to `hashes` the consistency path from the first `old_count` entries of
+    /// the subtree of the `count` entries from `start` to the whole subtree,
+    /// as `BinaryTree::consistency_path` does.
+    fn consistency_path(
+        &self,
+        old_count: usize,
+        start: usize,
+        count: usize,
+        complete: bool,
+        keep_complete: bool,
+        hashes: &mut Vec<Vec<u8>>,
+    ) {
+        if old_count == count {
+            if !complete || keep_complete {
+                hashes.push(self.subtree_hash(start, count));
+            }
+            return;
+        }
+        if count < 2 {
+            return;
+        }
+        let left_count = count.next_power_of_two() / 2;
+        if old_count <= left_count {
+            self.consistency_path(
+                old_count,
+                start,
+                left_count,
+                complete,
+                keep_complete,
+                hashes,
+            );
+            hashes.push(self.subtree_hash(start + left_count, count - left_count));
+        } else {
+            self.consistency_path(
+                old_count - left_count,
+                start + left_count,
+                count - left_count,
+                false,
+                keep_complete,
+                hashes,
+            );
+            hashes.push(self.subtree_hash(start, left_count));
+        }
+    }
+}
+
+impl LogHead {
+    /// Checks whether `proof` is an inclusion proof for the log at this head.
+    /// This does not validate the proof itself.
+    pub fn is_for_proof(&self, proof: &Proof<LogEntry>) -> bool {
+        std::ptr::eq(self.algorithm, proof.algorithm)
+            && self.profile == proof.profile
+            && digests_match(&self.root_hash, &proof.root_hash)
+            && proof.count.map_or(true, |count| count as u64 == self.size)
+    }
+
+    /// Checks whether `proof` shows that the log at `self` is a prefix of the log at `newer`.
+    pub fn is_consistent_with(&self, newer: &LogHead, proof: &ConsistencyProof) -> bool {
+        std::ptr::eq(self.algorithm, proof.algorithm)
+            && std::ptr::eq(newer.algorithm, proof.algorithm)
+            && self.profile == proof.profile
+            && newer.profile == proof.profile
+            && self.size == proof.old_size as u64
+            && newer.size == proof.new_size as u64
+            && proof.validate(&self.root_hash, &newer.root_hash)
+    }
+}
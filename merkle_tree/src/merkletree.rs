@@ -1,16 +1,44 @@
-use crate::hashing::{Hashable, Hashing};
-use crate::proof::{Conjecture, Proof};
+use crate::consistency::ConsistencyProof;
+use crate::hashing::{Hashable, Profile, TreeHasher};
+use crate::proof::{algorithm_serde, digests_match, Conjecture, Proof};
 use crate::tree::BinaryTree;
+use crate::visit::{self, Leaves, Visitor};
 use ring::digest::Algorithm;
+use ring::hmac;
+use serde_derive::{Deserialize, Serialize};
 use std::cmp::Ordering;
 
 /// A Merkle tree is a binary tree, with values of type `T` at the leafs,
 /// and where every internal node holds the hash of the concatenation of the hashes of its children nodes.
-#[derive(Clone, Debug)]
+///
+/// Deserializing a `MerkleTree` re-verifies every internal hash and the shape of the tree,
+/// so that a corrupted snapshot is rejected. Leaf values are not re-hashed, as `T` need
+/// not be `Hashable`: call `verify_leaves` to check them against their leaf hashes.
+///
+/// The hashes of a keyed tree, see `from_vec_keyed`, are HMAC tags under a key that
+/// is not part of the tree: its snapshots can only be checked for their shape.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedMerkleTree<T>")]
 pub struct MerkleTree<T> {
     /// The hashing algorithm used by this Merkle tree
+    #[serde(with = "algorithm_serde")]
     pub algorithm: &'static Algorithm,
 
+    /// How the root hash is derived from the inner binary tree
+    profile: Profile,
+
+    /// The root hash, derived from `root` according to `profile`
+    #[serde(skip_serializing)]
+    root_hash: Vec<u8>,
+
+    /// Whether the hashes are HMAC tags rather than plain digests
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    keyed: bool,
+
+    /// The key of a keyed tree, if known. It is never serialized.
+    #[serde(skip_serializing)]
+    key: Option<hmac::Key>,
+
     /// The root of the inner binary tree
     root: BinaryTree<T>,
 
@@ -21,12 +49,47 @@ pub struct MerkleTree<T> {
     count: usize,
 }
 
+/// A deserialized `MerkleTree` whose hashes have not been verified yet.
+#[derive(Deserialize)]
+struct UncheckedMerkleTree<T> {
+    #[serde(with = "algorithm_serde")]
+    algorithm: &'static Algorithm,
+    #[serde(default)]
+    profile: Profile,
+    #[serde(default)]
+    keyed: bool,
+    root: BinaryTree<T>,
+    height: usize,
+    count: usize,
+}
+
+impl<T> TryFrom<UncheckedMerkleTree<T>> for MerkleTree<T> {
+    type Error = String;
+
+    fn try_from(tree: UncheckedMerkleTree<T>) -> Result<Self, Self::Error> {
+        let (count, height) = tree.root.verify(tree.algorithm, tree.profile, tree.keyed)?;
+        if count != tree.count {
+            return Err(format!("expected {} leaves, found {}", tree.count, count));
+        }
+        if height != tree.height {
+            return Err(format!("expected height {}, found {}", tree.height, height));
+        }
+
+        let mut merkle_tree =
+            MerkleTree::new(tree.algorithm, tree.profile, tree.root, height, count);
+        merkle_tree.keyed = tree.keyed;
+        Ok(merkle_tree)
+    }
+}
+
 impl<T: PartialEq> PartialEq for MerkleTree<T> {
     #[allow(trivial_casts)]
     fn eq(&self, other: &MerkleTree<T>) -> bool {
         self.root == other.root
             && self.height == other.height
             && self.count == other.count
+            && self.profile == other.profile
+            && self.keyed == other.keyed
             && std::ptr::eq(self.algorithm, other.algorithm)
     }
 }
@@ -45,35 +108,119 @@ impl<T: Ord> Ord for MerkleTree<T> {
         self.height
             .cmp(&other.height)
             .then(self.count.cmp(&other.count))
+            .then(self.profile.cmp(&other.profile))
+            .then(self.keyed.cmp(&other.keyed))
             .then((self.algorithm as *const Algorithm).cmp(&(other.algorithm as *const Algorithm)))
             .then_with(|| self.root.cmp(&other.root))
     }
 }
 
 impl<T> MerkleTree<T> {
-    /// Constructs a Merkle Tree from a vector of data blocks.
-    /// Returns `None` if `values` is empty.
+    /// Constructs a Merkle Tree from a vector of data blocks,
+    /// with the `Legacy` hashing profile.
     pub fn from_vec(algorithm: &'static Algorithm, values: Vec<T>) -> Self
     where
         T: Hashable,
     {
-        if values.is_empty() {
-            return MerkleTree {
-                algorithm,
-                root: BinaryTree::empty(algorithm.hash_empty()),
-                height: 0,
-                count: 0,
-            };
-        }
+        MerkleTree::from_vec_with_profile(algorithm, Profile::Legacy, values)
+    }
 
-        let count = values.len();
-        let mut height = 0;
-        let mut cur = Vec::with_capacity(count);
+    /// Constructs a Merkle Tree from a vector of data blocks,
+    /// with the given hashing profile.
+    pub fn from_vec_with_profile(
+        algorithm: &'static Algorithm,
+        profile: Profile,
+        values: Vec<T>,
+    ) -> Self
+    where
+        T: Hashable,
+    {
+        let leaves = values
+            .into_iter()
+            .map(|v| BinaryTree::new_leaf(&algorithm, v))
+            .collect();
+        MerkleTree::from_leaves(&algorithm, profile, leaves)
+    }
+
+    /// Constructs a keyed Merkle Tree from a vector of data blocks,
+    /// with the given hashing profile.
+    ///
+    /// Every hash is an HMAC tag under `key`, see `TreeHasher`, so that the tree and
+    /// its proofs do not allow testing guesses of the values without the key.
+    /// The digest algorithm of the tree is that of the key.
+    pub fn from_vec_keyed(key: &hmac::Key, profile: Profile, values: Vec<T>) -> Self
+    where
+        T: Hashable,
+    {
+        let leaves = values
+            .into_iter()
+            .map(|v| BinaryTree::new_leaf(key, v))
+            .collect();
+        let mut tree = MerkleTree::from_leaves(key, profile, leaves);
+        tree.keyed = true;
+        tree.key = Some(key.clone());
+        tree
+    }
+
+    /// Constructs a Merkle Tree from leaves whose hashes were already computed,
+    /// e.g. with `Hashing::hash_leaf_reader` while streaming their content,
+    /// with the `Legacy` hashing profile.
+    /// Each hash must have been computed as a leaf hash with `algorithm`.
+    pub fn from_hashed_leaves<H: AsRef<[u8]>>(
+        algorithm: &'static Algorithm,
+        leaves: Vec<(H, T)>,
+    ) -> Self {
+        MerkleTree::from_hashed_leaves_with_profile(algorithm, Profile::Legacy, leaves)
+    }
+
+    /// Constructs a Merkle Tree from leaves whose hashes were already computed,
+    /// with the given hashing profile, e.g. the leaf hashes of a receipt.
+    /// Each hash must have been computed as a leaf hash with `algorithm`.
+    pub fn from_hashed_leaves_with_profile<H: AsRef<[u8]>>(
+        algorithm: &'static Algorithm,
+        profile: Profile,
+        leaves: Vec<(H, T)>,
+    ) -> Self {
+        let leaves = leaves
+            .into_iter()
+            .map(|(hash, value)| BinaryTree::new(hash.as_ref().to_vec(), value))
+            .collect();
+        MerkleTree::from_leaves(&algorithm, profile, leaves)
+    }
 
-        for v in values {
-            let leaf = BinaryTree::new_leaf(algorithm, v);
-            cur.push(leaf);
+    fn new(
+        algorithm: &'static Algorithm,
+        profile: Profile,
+        root: BinaryTree<T>,
+        height: usize,
+        count: usize,
+    ) -> Self {
+        MerkleTree {
+            algorithm,
+            profile,
+            root_hash: profile.root_hash(algorithm, count, root.hash()),
+            keyed: false,
+            key: None,
+            root,
+            height,
+            count,
         }
+    }
+
+    fn from_leaves<H: TreeHasher>(
+        hasher: &H,
+        profile: Profile,
+        leaves: Vec<BinaryTree<T>>,
+    ) -> Self {
+        let algorithm = hasher.algorithm();
+        if leaves.is_empty() {
+            let root = BinaryTree::empty(hasher.empty_hash(profile));
+            return MerkleTree::new(algorithm, profile, root, 0, 0);
+        }
+
+        let count = leaves.len();
+        let mut height = 0;
+        let mut cur = leaves;
 
         while cur.len() > 1 {
             let mut next = Vec::new();
@@ -84,10 +231,8 @@ impl<T> MerkleTree<T> {
                     let left = cur.remove(0);
                     let right = cur.remove(0);
 
-                    let combined_hash = algorithm.hash_nodes(left.hash(), right.hash());
-
                     let node = BinaryTree::Node {
-                        hash: combined_hash.as_ref().into(),
+                        hash: hasher.node_hash(left.hash(), right.hash()),
                         left: Box::new(left),
                         right: Box::new(right),
                     };
@@ -105,17 +250,57 @@ impl<T> MerkleTree<T> {
 
         let root = cur.remove(0);
 
-        MerkleTree {
-            algorithm,
-            root,
-            height,
-            count,
-        }
+        MerkleTree::new(algorithm, profile, root, height, count)
     }
 
     /// Returns the root hash of Merkle tree
     pub fn root_hash(&self) -> &Vec<u8> {
-        self.root.hash()
+        &self.root_hash
+    }
+
+    /// Returns the hashing profile of the Merkle tree
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
+
+    /// Returns whether the hashes of the Merkle tree are keyed
+    pub fn is_keyed(&self) -> bool {
+        self.keyed
+    }
+
+    /// Returns the hash of the leaf holding `value`, unless the tree is keyed
+    /// and its key is not known.
+    fn leaf_hash(&self, value: &T) -> Option<Vec<u8>>
+    where
+        T: Hashable,
+    {
+        match (self.keyed, &self.key) {
+            (false, _) => Some(self.algorithm.leaf_hash(value)),
+            (true, Some(key)) => Some(key.leaf_hash(value)),
+            (true, None) => None,
+        }
+    }
+
+    /// Checks that every leaf value hashes to its leaf hash, e.g. after deserializing
+    /// the tree. The values of a keyed tree can only be checked with its key, and are
+    /// taken as given otherwise.
+    pub fn verify_leaves(&self) -> Result<(), String>
+    where
+        T: Hashable,
+    {
+        for leaf in self.leaves() {
+            if let Some(hash) = self.leaf_hash(leaf.value) {
+                if !digests_match(&hash, leaf.hash) {
+                    return Err(format!("leaf {} does not match its hash", leaf.index));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the root of the inner binary tree
+    pub(crate) fn root(&self) -> &BinaryTree<T> {
+        &self.root
     }
 
     /// Returns the height of Merkle tree
@@ -135,24 +320,127 @@ impl<T> MerkleTree<T> {
 
     /// Generate an inclusion proof for the given value.
     /// Returns `None` if the given value is not found in the tree.
+    ///
+    /// If the value occurs more than once, the proof is for its first occurrence.
+    /// Use `gen_proofs` or `gen_nth_proof` when duplicates matter.
+    /// A keyed tree whose key is not known finds no value, see `gen_nth_proof`.
     pub fn gen_proof(&self, value: T) -> Option<Proof<T>>
     where
         T: Hashable,
     {
         let root_hash = self.root_hash().clone();
-        let leaf_hash = self.algorithm.hash_leaf(&value);
+        let leaf_hash = self.leaf_hash(&value)?;
+
+        self.root
+            .conjecture(leaf_hash.as_ref())
+            .map(|lemma| self.new_proof(root_hash, lemma, value))
+    }
+
+    /// Returns an iterator over the leaves of the tree, by increasing index.
+    pub fn leaves(&self) -> Leaves<'_, T> {
+        Leaves::new(&self.root)
+    }
+
+    /// Returns the hashes of every level of the tree, from the leaves (level `0`)
+    /// up to the root (level `height`). An empty tree has no levels.
+    ///
+    /// Level `k` holds the hashes of the subtrees covering `2^k` consecutive leaves,
+    /// the last one possibly covering fewer. A node without a sibling is promoted,
+    /// and so appears on several levels.
+    pub fn levels(&self) -> Vec<Vec<&[u8]>> {
+        visit::levels(&self.root, self.count, self.height)
+    }
 
-        Conjecture::new(&self.root, leaf_hash.as_ref())
-            .map(|lemma| Proof::new(self.algorithm, root_hash, lemma, value))
+    /// Walks the tree depth-first, from left to right, calling `visitor` on every node.
+    pub fn walk<V>(&self, visitor: &mut V)
+    where
+        V: Visitor<T> + ?Sized,
+    {
+        visit::walk(&self.root, 0, 0, self.count, visitor);
+    }
+
+    /// Returns the indices of every leaf whose hash matches the hash of `value`,
+    /// in increasing order.
+    /// A keyed tree whose key is not known finds no value.
+    pub fn indices_of(&self, value: &T) -> Vec<usize>
+    where
+        T: Hashable,
+    {
+        let mut indices = Vec::new();
+        if let Some(leaf_hash) = self.leaf_hash(value) {
+            self.root
+                .collect_leaf_indices(&leaf_hash, &mut 0, &mut indices);
+        }
+        indices
+    }
+
+    /// Generate an inclusion proof for every leaf matching the given value,
+    /// ordered by leaf index.
+    /// Returns an empty vector if the given value is not found in the tree.
+    pub fn gen_proofs(&self, value: &T) -> Vec<Proof<T>>
+    where
+        T: Hashable + Clone,
+    {
+        self.indices_of(value)
+            .into_iter()
+            .filter_map(|n| self.gen_nth_proof(n))
+            .collect()
     }
 
     /// Generate an inclusion proof for the `n`-th leaf value.
     pub fn gen_nth_proof(&self, n: usize) -> Option<Proof<T>>
     where
-        T: Hashable + Clone,
+        T: Clone,
     {
         let root_hash = self.root_hash().clone();
-        Conjecture::new_by_index(&self.root, n, self.count)
-            .map(|(lemma, value)| Proof::new(self.algorithm, root_hash, lemma, value.clone()))
+        self.root
+            .conjecture_by_index(n, self.count)
+            .map(|(lemma, value)| self.new_proof(root_hash, lemma, value.clone()))
+    }
+
+    /// Generate an inclusion proof for every leaf value, ordered by leaf index.
+    ///
+    /// This walks the tree once, which is cheaper than calling `gen_nth_proof`
+    /// for each index when exporting proofs for the whole tree.
+    pub fn gen_all_proofs(&self) -> Vec<Proof<T>>
+    where
+        T: Clone,
+    {
+        let root_hash = self.root_hash();
+        self.root
+            .all_conjectures()
+            .into_iter()
+            .map(|(lemma, value)| self.new_proof(root_hash.clone(), lemma, value.clone()))
+            .collect()
+    }
+
+    /// Generate a consistency proof showing that the tree made of the first `old_count`
+    /// leaves of this tree is a prefix of this tree.
+    /// Returns `None` if `old_count` is larger than the number of leaves, or if the tree is keyed.
+    pub fn gen_consistency_proof(&self, old_count: usize) -> Option<ConsistencyProof> {
+        if old_count > self.count || self.keyed {
+            return None;
+        }
+
+        let mut hashes = Vec::new();
+        if 0 < old_count && old_count < self.count {
+            let keep_complete = self.profile == Profile::Hardened;
+            self.root
+                .consistency_path(old_count, self.count, true, keep_complete, &mut hashes);
+        }
+        Some(ConsistencyProof {
+            algorithm: self.algorithm,
+            profile: self.profile,
+            old_size: old_count,
+            new_size: self.count,
+            hashes,
+        })
+    }
+
+    fn new_proof(&self, root_hash: Vec<u8>, lemma: Conjecture, value: T) -> Proof<T> {
+        let mut proof = Proof::new(self.algorithm, root_hash, lemma, value)
+            .with_profile(self.profile, self.count);
+        proof.keyed = self.keyed;
+        proof
     }
 }
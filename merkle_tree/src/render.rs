@@ -0,0 +1,240 @@
+//! Renders a `MerkleTree` or a `Proof` as Graphviz DOT or as an indented ASCII tree,
+//! with hashes truncated to their first bytes and the audit path highlighted.
+
+use crate::merkletree::MerkleTree;
+use crate::proof::{Conjecture, Proof, Side};
+use crate::tree::BinaryTree;
+use std::fmt::Write;
+
+/// The number of leading bytes of a hash that are shown.
+const HASH_PREFIX_LEN: usize = 4;
+
+/// Returns the hex encoding of the first bytes of `hash`,
+/// followed by `..` if it was truncated.
+pub fn short_hex(hash: &[u8]) -> String {
+    let mut hex = hash
+        .iter()
+        .take(HASH_PREFIX_LEN)
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        });
+    if hash.len() > HASH_PREFIX_LEN {
+        hex.push_str("..");
+    }
+    hex
+}
+
+/// Renders `tree` as an indented ASCII tree.
+/// If `leaf` is given, the audit path of that leaf is highlighted.
+pub fn tree_to_ascii<T>(tree: &MerkleTree<T>, leaf: Option<usize>) -> String {
+    let mut out = String::new();
+    tree_item(tree, leaf).write_ascii("", "", &mut out);
+    out
+}
+
+/// Renders `tree` as a Graphviz DOT graph.
+/// If `leaf` is given, the audit path of that leaf is highlighted.
+pub fn tree_to_dot<T>(tree: &MerkleTree<T>, leaf: Option<usize>) -> String {
+    let title = format!(
+        "{:?} Merkle tree, {} leaves, height {}",
+        tree.algorithm,
+        tree.count(),
+        tree.height()
+    );
+    to_dot(&title, &tree_item(tree, leaf))
+}
+
+/// Renders `proof` as an indented ASCII tree, from the root down to the proven leaf.
+pub fn proof_to_ascii<T>(proof: &Proof<T>) -> String {
+    let mut out = format!(
+        "{:?} proof for root {}\n",
+        proof.algorithm,
+        short_hex(&proof.root_hash)
+    );
+    proof_item(&proof.conjecture, true).write_ascii("", "", &mut out);
+    out
+}
+
+/// Renders `proof` as a Graphviz DOT graph, from the root down to the proven leaf.
+pub fn proof_to_dot<T>(proof: &Proof<T>) -> String {
+    let title = format!(
+        "{:?} proof for root {}",
+        proof.algorithm,
+        short_hex(&proof.root_hash)
+    );
+    to_dot(&title, &proof_item(&proof.conjecture, true))
+}
+
+/// How a node relates to the audit path being highlighted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Role {
+    /// The node is on the path from the root to the proven leaf.
+    Path,
+    /// The node is the sibling of a node on the path.
+    Sibling,
+    /// Any other node.
+    Other,
+}
+
+/// A node to render, independently of whether it comes from a tree or a proof.
+struct Item {
+    label: String,
+    role: Role,
+    children: Vec<Item>,
+}
+
+impl Item {
+    fn marker(&self) -> &'static str {
+        match self.role {
+            Role::Path => " [path]",
+            Role::Sibling => " [sibling]",
+            Role::Other => "",
+        }
+    }
+
+    fn write_ascii(&self, connector: &str, prefix: &str, out: &mut String) {
+        let _ = writeln!(
+            out,
+            "{}{}{}{}",
+            prefix,
+            connector,
+            self.label,
+            self.marker()
+        );
+
+        let child_prefix = match connector {
+            "" => prefix.to_string(),
+            "\\-- " => format!("{}    ", prefix),
+            _ => format!("{}|   ", prefix),
+        };
+        for (i, child) in self.children.iter().enumerate() {
+            let connector = if i + 1 == self.children.len() {
+                "\\-- "
+            } else {
+                "+-- "
+            };
+            child.write_ascii(connector, &child_prefix, out);
+        }
+    }
+
+    fn write_dot(&self, next_id: &mut usize, out: &mut String) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+
+        let style = match self.role {
+            Role::Path => ", style=filled, fillcolor=lightblue",
+            Role::Sibling => ", style=filled, fillcolor=lightgrey",
+            Role::Other => "",
+        };
+        let _ = writeln!(out, "    n{} [label=\"{}\"{}];", id, self.label, style);
+
+        for child in &self.children {
+            let child_id = child.write_dot(next_id, out);
+            let style = if self.role == Role::Path && child.role == Role::Path {
+                " [color=blue, penwidth=2]"
+            } else {
+                ""
+            };
+            let _ = writeln!(out, "    n{} -> n{}{};", id, child_id, style);
+        }
+        id
+    }
+}
+
+fn to_dot(title: &str, root: &Item) -> String {
+    let mut out = String::from("digraph merkle {\n");
+    let _ = writeln!(out, "    label=\"{}\";", title);
+    out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+    root.write_dot(&mut 0, &mut out);
+    out.push_str("}\n");
+    out
+}
+
+fn tree_item<T>(tree: &MerkleTree<T>, leaf: Option<usize>) -> Item {
+    let leaf = leaf.filter(|&leaf| leaf < tree.count());
+    let role = if leaf.is_some() {
+        Role::Path
+    } else {
+        Role::Other
+    };
+    node_item(tree.root(), 0, tree.count(), leaf, role)
+}
+
+/// `offset` is the index of the first leaf of `node`, and `count` its number of leaves.
+fn node_item<T>(
+    node: &BinaryTree<T>,
+    offset: usize,
+    count: usize,
+    leaf: Option<usize>,
+    role: Role,
+) -> Item {
+    match *node {
+        BinaryTree::Empty { ref hash } => Item {
+            label: format!("empty {}", short_hex(hash)),
+            role,
+            children: Vec::new(),
+        },
+        BinaryTree::Leaf { ref hash, .. } => Item {
+            label: format!("leaf {} {}", offset, short_hex(hash)),
+            role,
+            children: Vec::new(),
+        },
+        BinaryTree::Node {
+            ref hash,
+            ref left,
+            ref right,
+        } => {
+            let left_count = count.next_power_of_two() / 2;
+            let (left_role, right_role) = match (role, leaf) {
+                (Role::Path, Some(leaf)) if leaf < offset + left_count => {
+                    (Role::Path, Role::Sibling)
+                }
+                (Role::Path, Some(_)) => (Role::Sibling, Role::Path),
+                _ => (Role::Other, Role::Other),
+            };
+            Item {
+                label: format!("node {}", short_hex(hash)),
+                role,
+                children: vec![
+                    node_item(left, offset, left_count, leaf, left_role),
+                    node_item(
+                        right,
+                        offset + left_count,
+                        count - left_count,
+                        leaf,
+                        right_role,
+                    ),
+                ],
+            }
+        }
+    }
+}
+
+fn proof_item(conjecture: &Conjecture, is_root: bool) -> Item {
+    let kind = match (is_root, &conjecture.sub_conjecture) {
+        (true, _) => "root",
+        (false, None) => "leaf",
+        (false, Some(_)) => "node",
+    };
+    let label = format!("{} {}", kind, short_hex(&conjecture.node_hash));
+
+    let sibling = |hash: &Vec<u8>| Item {
+        label: format!("node {}", short_hex(hash)),
+        role: Role::Sibling,
+        children: Vec::new(),
+    };
+    let children = match (&conjecture.sub_conjecture, &conjecture.sibling_hash) {
+        (Some(sub), Some(Side::Left(hash))) => vec![sibling(hash), proof_item(sub, false)],
+        (Some(sub), Some(Side::Right(hash))) => vec![proof_item(sub, false), sibling(hash)],
+        (Some(sub), None) => vec![proof_item(sub, false)],
+        (None, Some(Side::Left(hash))) | (None, Some(Side::Right(hash))) => vec![sibling(hash)],
+        (None, None) => Vec::new(),
+    };
+
+    Item {
+        label,
+        role: Role::Path,
+        children,
+    }
+}
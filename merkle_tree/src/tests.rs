@@ -1,10 +1,20 @@
 #![cfg(test)]
+// The original tests borrow and compare booleans more than clippy likes.
+#![allow(clippy::needless_borrow, clippy::bool_assert_comparison)]
 
 extern crate serde_json;
 
-use crate::hashing::Hashing;
+use crate::hashing::{Hashing, Profile, TreeHasher};
+use crate::head::TreeHead;
+use crate::log::Log;
 use crate::merkletree::MerkleTree;
-use ring::digest::{Algorithm, SHA512};
+use crate::proof::Side;
+use crate::render::{proof_to_ascii, proof_to_dot, short_hex, tree_to_ascii, tree_to_dot};
+use crate::visit::{Leaf, Node, Visitor};
+use proptest::prelude::*;
+use ring::digest::{Algorithm, Digest, SHA512};
+use ring::hmac;
+use ring::signature::{Ed25519KeyPair, KeyPair};
 
 static DIGEST: &Algorithm = &SHA512;
 
@@ -94,6 +104,79 @@ fn test_nth_proof() {
     }
 }
 
+#[test]
+fn test_all_proofs() {
+    assert!(MerkleTree::<Vec<u8>>::from_vec(DIGEST, vec![])
+        .gen_all_proofs()
+        .is_empty());
+
+    for &count in &[1, 2, 3, 10, 15, 16, 17, 22] {
+        let values = (1..=count).map(|x| vec![x as u8]).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(DIGEST, values);
+
+        let proofs = tree.gen_all_proofs();
+        assert_eq!(proofs.len(), count);
+
+        for (i, proof) in proofs.into_iter().enumerate() {
+            assert!(proof.validate(tree.root_hash()));
+            assert_eq!(i, proof.index(tree.count()));
+            assert_eq!(Some(proof), tree.gen_nth_proof(i));
+        }
+    }
+}
+
+#[test]
+fn test_duplicate_values() {
+    let values = vec![vec![1], vec![2], vec![1], vec![3], vec![1]];
+    let tree = MerkleTree::from_vec(DIGEST, values);
+    let root_hash = tree.root_hash();
+
+    assert_eq!(tree.indices_of(&vec![1]), vec![0, 2, 4]);
+    assert_eq!(tree.indices_of(&vec![3]), vec![3]);
+    assert!(tree.indices_of(&vec![4]).is_empty());
+
+    let proofs = tree.gen_proofs(&vec![1]);
+    assert_eq!(proofs.len(), 3);
+    for (proof, expected) in proofs.iter().zip([0, 2, 4]) {
+        assert!(proof.validate(root_hash));
+        assert_eq!(proof.index(tree.count()), expected);
+    }
+
+    assert!(tree.gen_proofs(&vec![4]).is_empty());
+}
+
+#[test]
+fn test_from_hashed_leaves() {
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(DIGEST, values.clone());
+
+    let leaves = values
+        .iter()
+        .map(|v| (DIGEST.hash_leaf(v), ()))
+        .collect::<Vec<_>>();
+    let hashed = MerkleTree::from_hashed_leaves(DIGEST, leaves);
+
+    assert_eq!(hashed.root_hash(), tree.root_hash());
+    assert_eq!(hashed.height(), tree.height());
+    assert_eq!(hashed.count(), tree.count());
+
+    for (i, value) in values.iter().enumerate() {
+        let proof = hashed.gen_nth_proof(i).expect("gen proof by index");
+        assert!(proof.validate(tree.root_hash()));
+        assert!(proof.is_for_leaf(DIGEST.hash_leaf(value).as_ref()));
+        assert!(!proof.is_for_leaf(DIGEST.hash_leaf(&vec![0]).as_ref()));
+    }
+
+    // Hashes may also be given as bytes, e.g. as received from a server.
+    let tree = MerkleTree::from_vec_with_profile(DIGEST, Profile::Hardened, values.clone());
+    let leaves = values
+        .iter()
+        .map(|v| (DIGEST.hash_leaf(v).as_ref().to_vec(), ()))
+        .collect::<Vec<_>>();
+    let hashed = MerkleTree::from_hashed_leaves_with_profile(DIGEST, Profile::Hardened, leaves);
+    assert_eq!(hashed.root_hash(), tree.root_hash());
+}
+
 #[test]
 fn test_serialize_proof_with_serde() {
     let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
@@ -107,3 +190,729 @@ fn test_serialize_proof_with_serde() {
         serde_json::from_str(&serialized).expect("deserialize proof")
     );
 }
+
+#[test]
+fn test_proof_root_mismatch() {
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(DIGEST, values);
+    let proof = tree.gen_nth_proof(4).expect("gen proof by index");
+
+    let mut flipped_last = tree.root_hash().clone();
+    *flipped_last.last_mut().unwrap() ^= 1;
+    let mut flipped_first = tree.root_hash().clone();
+    flipped_first[0] ^= 1;
+    let truncated = &tree.root_hash()[..tree.root_hash().len() - 1];
+
+    assert!(proof.validate(tree.root_hash()));
+    assert!(!proof.validate(&flipped_last));
+    assert!(!proof.validate(&flipped_first));
+    assert!(!proof.validate(truncated));
+}
+
+#[test]
+fn test_proof_tampered_sibling() {
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(DIGEST, values);
+    let mut proof = tree.gen_nth_proof(4).expect("gen proof by index");
+
+    match proof.conjecture.sibling_hash {
+        Some(Side::Left(ref mut hash)) | Some(Side::Right(ref mut hash)) => hash[0] ^= 1,
+        None => unreachable!("a tree of nine leaves has siblings"),
+    }
+
+    assert!(!proof.validate(tree.root_hash()));
+}
+
+#[test]
+fn test_serialize_tree_with_serde() {
+    for &count in &[0, 1, 2, 3, 10, 17] {
+        let values = (1..=count).map(|x| vec![x as u8]).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(DIGEST, values);
+
+        let serialized = serde_json::to_string(&tree).expect("serialize tree");
+        let deserialized: MerkleTree<Vec<u8>> =
+            serde_json::from_str(&serialized).expect("deserialize tree");
+
+        assert_eq!(tree, deserialized);
+        assert_eq!(tree.root_hash(), deserialized.root_hash());
+    }
+}
+
+#[test]
+fn test_deserialize_corrupted_tree() {
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(DIGEST, values);
+    let snapshot = serde_json::to_value(&tree).expect("serialize tree");
+
+    let corruptions: Vec<fn(&mut serde_json::Value)> = vec![
+        // Root hash.
+        |v| flip_hash(&mut v["root"]["Node"]["hash"]),
+        // Internal hash.
+        |v| flip_hash(&mut v["root"]["Node"]["left"]["Node"]["hash"]),
+        // Leaf hash, caught by its parent.
+        |v| flip_hash(&mut v["root"]["Node"]["right"]["Leaf"]["hash"]),
+        // Leaf count.
+        |v| v["count"] = 8.into(),
+        // Height.
+        |v| v["height"] = 5.into(),
+        // Algorithm.
+        |v| v["algorithm"] = "SHA256".into(),
+        // Shape: swap the children of the root.
+        |v| {
+            let node = &mut v["root"]["Node"];
+            let left = node["left"].take();
+            node["left"] = node["right"].take();
+            node["right"] = left;
+        },
+    ];
+
+    for corrupt in corruptions {
+        let mut corrupted = snapshot.clone();
+        corrupt(&mut corrupted);
+        assert!(serde_json::from_value::<MerkleTree<Vec<u8>>>(corrupted).is_err());
+    }
+}
+
+#[test]
+fn test_verify_leaves_of_deserialized_tree() {
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(DIGEST, values);
+    let mut snapshot = serde_json::to_value(&tree).expect("serialize tree");
+    let deserialized: MerkleTree<Vec<u8>> =
+        serde_json::from_value(snapshot.clone()).expect("deserialize tree");
+    assert!(deserialized.verify_leaves().is_ok());
+
+    // A changed value keeps every hash consistent, and is only caught by its leaf hash.
+    snapshot["root"]["Node"]["right"]["Leaf"]["value"] = serde_json::json!([42]);
+    let corrupted: MerkleTree<Vec<u8>> =
+        serde_json::from_value(snapshot).expect("deserialize tree");
+    assert_eq!(
+        corrupted.verify_leaves(),
+        Err("leaf 8 does not match its hash".to_string())
+    );
+
+    // Without its key, the values of a keyed tree cannot be checked.
+    let key = hmac::Key::new(hmac::HMAC_SHA512, b"key");
+    let keyed = MerkleTree::from_vec_keyed(&key, Profile::Legacy, vec![vec![1], vec![2]]);
+    let mut snapshot = serde_json::to_value(&keyed).expect("serialize tree");
+    snapshot["root"]["Node"]["left"]["Leaf"]["value"] = serde_json::json!([42]);
+    let corrupted: MerkleTree<Vec<u8>> =
+        serde_json::from_value(snapshot).expect("deserialize tree");
+    assert!(corrupted.verify_leaves().is_ok());
+}
+
+fn flip_hash(hash: &mut serde_json::Value) {
+    let byte = hash[0].as_u64().expect("hash byte");
+    hash[0] = (byte ^ 1).into();
+}
+
+#[test]
+fn test_short_hex() {
+    assert_eq!(short_hex(&[0x01, 0xab, 0x00, 0xff, 0x10]), "01ab00ff..");
+    assert_eq!(short_hex(&[0x01, 0xab]), "01ab");
+}
+
+#[test]
+fn test_tree_to_ascii() {
+    let values = vec![vec![1], vec![2], vec![3]];
+    let tree = MerkleTree::from_vec(DIGEST, values.clone());
+
+    let leaf = |i: usize| short_hex(DIGEST.hash_leaf(&values[i]).as_ref());
+    let h01 = DIGEST.hash_nodes(&DIGEST.hash_leaf(&values[0]), &DIGEST.hash_leaf(&values[1]));
+    let root = short_hex(tree.root_hash());
+
+    let expected = format!(
+        "node {root} [path]\n\
+         +-- node {h01} [path]\n\
+         |   +-- leaf 0 {l0} [sibling]\n\
+         |   \\-- leaf 1 {l1} [path]\n\
+         \\-- leaf 2 {l2} [sibling]\n",
+        root = root,
+        h01 = short_hex(h01.as_ref()),
+        l0 = leaf(0),
+        l1 = leaf(1),
+        l2 = leaf(2),
+    );
+    assert_eq!(tree_to_ascii(&tree, Some(1)), expected);
+
+    let plain = tree_to_ascii(&tree, None);
+    assert!(!plain.contains("[path]") && !plain.contains("[sibling]"));
+    assert_eq!(plain, tree_to_ascii(&tree, Some(3)));
+}
+
+#[test]
+fn test_tree_to_dot() {
+    let values = (1..6).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(DIGEST, values);
+    let dot = tree_to_dot(&tree, Some(4));
+
+    assert!(dot.starts_with("digraph merkle {\n"));
+    assert!(dot.ends_with("}\n"));
+    // Root, inner node, leaf 4 on the path; the left subtree as sibling.
+    assert_eq!(dot.matches("fillcolor=lightblue").count(), 2);
+    assert_eq!(dot.matches("fillcolor=lightgrey").count(), 1);
+    assert_eq!(dot.matches(" -> ").count(), 8);
+    assert!(dot.contains(&format!(
+        "label=\"leaf 4 {}\"",
+        short_hex(DIGEST.hash_leaf(&vec![5]).as_ref())
+    )));
+}
+
+#[test]
+fn test_proof_rendering() {
+    let values = (1..6).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(DIGEST, values);
+    let proof = tree.gen_nth_proof(2).expect("gen proof by index");
+
+    let ascii = proof_to_ascii(&proof);
+    let mut lines = ascii.lines();
+    assert_eq!(
+        lines.next(),
+        Some(format!("SHA512 proof for root {}", short_hex(tree.root_hash())).as_str())
+    );
+    assert_eq!(
+        lines.next(),
+        Some(format!("root {} [path]", short_hex(tree.root_hash())).as_str())
+    );
+    assert_eq!(ascii.matches("[sibling]").count(), 3);
+    assert!(ascii.contains(&format!("leaf {} [path]", short_hex(proof.leaf_hash()))));
+
+    let dot = proof_to_dot(&proof);
+    assert_eq!(dot.matches("fillcolor=lightblue").count(), 4);
+    assert_eq!(dot.matches("fillcolor=lightgrey").count(), 3);
+}
+
+#[test]
+fn test_leaves() {
+    assert_eq!(
+        MerkleTree::<Vec<u8>>::from_vec(DIGEST, vec![])
+            .leaves()
+            .count(),
+        0
+    );
+
+    for &count in &[1, 2, 3, 10, 17] {
+        let values = (0..count).map(|x| vec![x as u8]).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(DIGEST, values.clone());
+
+        let leaves = tree.leaves().collect::<Vec<_>>();
+        assert_eq!(leaves.len(), count);
+        for (i, leaf) in leaves.into_iter().enumerate() {
+            assert_eq!(leaf.index, i);
+            assert_eq!(leaf.value, &values[i]);
+            assert_eq!(leaf.hash, DIGEST.hash_leaf(&values[i]).as_ref());
+        }
+    }
+}
+
+#[test]
+fn test_levels() {
+    assert!(MerkleTree::<Vec<u8>>::from_vec(DIGEST, vec![])
+        .levels()
+        .is_empty());
+
+    for &count in &[1, 2, 3, 5, 10, 16, 17] {
+        let values = (0..count).map(|x| vec![x as u8]).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(DIGEST, values.clone());
+        let levels = tree.levels();
+
+        assert_eq!(levels.len(), tree.height() + 1);
+        assert_eq!(levels[tree.height()], vec![tree.root_hash().as_slice()]);
+
+        // Recompute every level from the leaves, as `from_vec` does.
+        let mut expected = values
+            .iter()
+            .map(|v| DIGEST.hash_leaf(v).as_ref().to_vec())
+            .collect::<Vec<_>>();
+        for level in levels {
+            assert_eq!(
+                level,
+                expected.iter().map(Vec::as_slice).collect::<Vec<_>>()
+            );
+            expected = expected
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => DIGEST.hash_nodes(left, right).as_ref().to_vec(),
+                    [single] => single.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+    }
+}
+
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+}
+
+impl Visitor<Vec<u8>> for Recorder {
+    fn visit_node(&mut self, node: Node<'_>) {
+        self.events.push(format!(
+            "enter {}..{} @{}",
+            node.first_leaf,
+            node.first_leaf + node.leaf_count,
+            node.depth
+        ));
+    }
+
+    fn leave_node(&mut self, node: Node<'_>) {
+        assert_eq!(
+            node.hash,
+            DIGEST
+                .hash_nodes(&node.left_hash, &node.right_hash)
+                .as_ref()
+        );
+        self.events.push(format!("leave {}", node.first_leaf));
+    }
+
+    fn visit_leaf(&mut self, leaf: Leaf<'_, Vec<u8>>, depth: usize) {
+        self.events
+            .push(format!("leaf {}={:?} @{}", leaf.index, leaf.value, depth));
+    }
+
+    fn visit_empty(&mut self, _hash: &[u8]) {
+        self.events.push("empty".to_string());
+    }
+}
+
+#[test]
+fn test_walk() {
+    let values = vec![vec![10], vec![11], vec![12]];
+    let tree = MerkleTree::from_vec(DIGEST, values);
+    let mut recorder = Recorder::default();
+    tree.walk(&mut recorder);
+
+    assert_eq!(
+        recorder.events,
+        vec![
+            "enter 0..3 @0",
+            "enter 0..2 @1",
+            "leaf 0=[10] @2",
+            "leaf 1=[11] @2",
+            "leave 0",
+            "leaf 2=[12] @1",
+            "leave 0",
+        ]
+    );
+
+    let mut recorder = Recorder::default();
+    MerkleTree::from_vec(DIGEST, vec![]).walk(&mut recorder);
+    assert_eq!(recorder.events, vec!["empty"]);
+}
+
+/// Hashes every pair of adjacent `hashes`, promoting the last one if it has no sibling,
+/// the way `from_hashed_leaves` builds the level above the leaves.
+fn hash_level(hashes: &[Digest]) -> Vec<Digest> {
+    hashes
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => DIGEST.hash_nodes(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn root_of_hashes(profile: Profile, hashes: &[Digest]) -> Vec<u8> {
+    let leaves = hashes.iter().map(|hash| (*hash, ())).collect();
+    MerkleTree::from_hashed_leaves_with_profile(DIGEST, profile, leaves)
+        .root_hash()
+        .clone()
+}
+
+#[test]
+fn test_hardened_empty_tree() {
+    let legacy = MerkleTree::<Vec<u8>>::from_vec(DIGEST, vec![]);
+    let hardened = MerkleTree::<Vec<u8>>::from_vec_with_profile(DIGEST, Profile::Hardened, vec![]);
+
+    assert_eq!(legacy.root_hash().as_slice(), DIGEST.hash_empty().as_ref());
+    assert_ne!(hardened.root_hash(), legacy.root_hash());
+    assert_eq!(
+        hardened.root_hash(),
+        &Profile::Hardened.root_hash(DIGEST, 0, Profile::Hardened.hash_empty(DIGEST).as_ref())
+    );
+    // The empty hardened tree does not collide with a tree holding an empty leaf either.
+    let empty_leaf = MerkleTree::from_vec_with_profile(DIGEST, Profile::Hardened, vec![vec![]]);
+    assert_ne!(hardened.root_hash(), empty_leaf.root_hash());
+}
+
+#[test]
+fn test_hardened_root_commits_to_count() {
+    let hashes = (0..5u8)
+        .map(|x| DIGEST.hash_leaf(&vec![x]))
+        .collect::<Vec<_>>();
+    let level = hash_level(&hashes);
+
+    // Passing off the level above the leaves as leaves gives the same legacy root...
+    assert_eq!(
+        root_of_hashes(Profile::Legacy, &hashes),
+        root_of_hashes(Profile::Legacy, &level)
+    );
+    // ...but not the same hardened root.
+    assert_ne!(
+        root_of_hashes(Profile::Hardened, &hashes),
+        root_of_hashes(Profile::Hardened, &level)
+    );
+}
+
+#[test]
+fn test_hardened_proofs() {
+    let values = (0..7u8).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec_with_profile(DIGEST, Profile::Hardened, values);
+    let root_hash = tree.root_hash();
+
+    for (i, proof) in tree.gen_all_proofs().into_iter().enumerate() {
+        assert_eq!(proof.profile, Profile::Hardened);
+        assert_eq!(proof.count, Some(7));
+        assert!(proof.validate(root_hash));
+        assert_eq!(proof.index(7), i);
+
+        let mut wrong_count = proof.clone();
+        wrong_count.count = Some(8);
+        assert!(!wrong_count.validate(root_hash));
+
+        let mut no_count = proof.clone();
+        no_count.count = None;
+        assert!(!no_count.validate(root_hash));
+
+        let mut legacy = proof;
+        legacy.profile = Profile::Legacy;
+        assert!(!legacy.validate(root_hash));
+    }
+
+    let serialized = serde_json::to_string(&tree).expect("serialize tree");
+    let deserialized: MerkleTree<Vec<u8>> =
+        serde_json::from_str(&serialized).expect("deserialize tree");
+    assert_eq!(deserialized, tree);
+    assert_eq!(deserialized.root_hash(), root_hash);
+}
+
+proptest! {
+    #[test]
+    fn prop_hardened_roots_differ_for_distinct_leaves(
+        a in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..4), 0..12),
+        b in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..4), 0..12),
+    ) {
+        prop_assume!(a != b);
+        let tree_a = MerkleTree::from_vec_with_profile(DIGEST, Profile::Hardened, a);
+        let tree_b = MerkleTree::from_vec_with_profile(DIGEST, Profile::Hardened, b);
+        prop_assert_ne!(tree_a.root_hash(), tree_b.root_hash());
+    }
+
+    #[test]
+    fn prop_hardened_roots_differ_for_internal_nodes_as_leaves(
+        values in prop::collection::vec(any::<u8>(), 2..40),
+    ) {
+        let hashes = values
+            .iter()
+            .map(|x| DIGEST.hash_leaf(&vec![*x]))
+            .collect::<Vec<_>>();
+        let level = hash_level(&hashes);
+
+        prop_assert_eq!(
+            root_of_hashes(Profile::Legacy, &hashes),
+            root_of_hashes(Profile::Legacy, &level)
+        );
+        prop_assert_ne!(
+            root_of_hashes(Profile::Hardened, &hashes),
+            root_of_hashes(Profile::Hardened, &level)
+        );
+    }
+
+    #[test]
+    fn prop_hardened_proofs_only_validate_with_their_count(
+        count in 1usize..40,
+        other in 1usize..40,
+    ) {
+        let values = (0..count).map(|x| x.to_be_bytes().to_vec()).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec_with_profile(DIGEST, Profile::Hardened, values);
+
+        for proof in tree.gen_all_proofs() {
+            prop_assert!(proof.validate(tree.root_hash()));
+
+            let mut forged = proof;
+            forged.count = Some(other);
+            prop_assert_eq!(forged.validate(tree.root_hash()), other == count);
+        }
+    }
+}
+
+fn hmac_key(byte: u8) -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA512, &[byte; 64])
+}
+
+#[test]
+fn test_keyed_tree() {
+    let values = (0..5u8).map(|x| vec![x]).collect::<Vec<_>>();
+    let key = hmac_key(1);
+    let tree = MerkleTree::from_vec_keyed(&key, Profile::Hardened, values.clone());
+    let plain = MerkleTree::from_vec_with_profile(DIGEST, Profile::Hardened, values.clone());
+
+    assert!(tree.is_keyed());
+    assert!(std::ptr::eq(tree.algorithm, DIGEST));
+    assert_ne!(tree.root_hash(), plain.root_hash());
+    assert_eq!(
+        tree.leaves().next().unwrap().hash,
+        key.leaf_hash(&values[0])
+    );
+
+    for (i, proof) in tree.gen_all_proofs().into_iter().enumerate() {
+        assert!(proof.keyed);
+        assert!(proof.validate_with_key(tree.root_hash(), &key));
+        assert!(proof.is_for_value_with_key(&key));
+        assert!(!proof.validate_with_key(tree.root_hash(), &hmac_key(2)));
+        assert!(!proof.is_for_value_with_key(&hmac_key(2)));
+        // A keyed proof is useless without the key.
+        assert!(!proof.validate(tree.root_hash()));
+        assert!(!proof.is_for_value());
+        assert!(!proof.is_for_leaf(DIGEST.hash_leaf(&values[i]).as_ref()));
+    }
+    assert_eq!(tree.gen_proof(vec![3]).map(|proof| proof.index(5)), Some(3));
+
+    // Plain proofs do not validate as keyed ones.
+    let plain_proof = plain.gen_nth_proof(0).unwrap();
+    assert!(!plain_proof.validate_with_key(plain.root_hash(), &key));
+}
+
+#[test]
+fn test_serialize_keyed_tree() {
+    let values = (0..5u8).map(|x| vec![x]).collect::<Vec<_>>();
+    let key = hmac_key(1);
+    let tree = MerkleTree::from_vec_keyed(&key, Profile::Hardened, values);
+
+    let serialized = serde_json::to_value(&tree).expect("serialize tree");
+    assert_eq!(serialized["keyed"], serde_json::Value::Bool(true));
+    let deserialized: MerkleTree<Vec<u8>> =
+        serde_json::from_value(serialized.clone()).expect("deserialize tree");
+    assert_eq!(deserialized, tree);
+    assert_eq!(deserialized.root_hash(), tree.root_hash());
+
+    // The key is not serialized, so values can no longer be looked up...
+    assert!(deserialized.gen_proof(vec![3]).is_none());
+    assert!(deserialized.indices_of(&vec![3]).is_empty());
+    // ...but proofs by index still validate with the key.
+    let proof = deserialized.gen_nth_proof(3).unwrap();
+    assert!(proof.validate_with_key(tree.root_hash(), &key));
+
+    // Hashes cannot be checked without the key, but the shape still is.
+    let mut unbalanced = serialized;
+    let left = unbalanced["root"]["Node"]["left"].take();
+    unbalanced["root"]["Node"]["left"] = unbalanced["root"]["Node"]["right"].take();
+    unbalanced["root"]["Node"]["right"] = left;
+    assert!(serde_json::from_value::<MerkleTree<Vec<u8>>>(unbalanced).is_err());
+}
+
+#[test]
+fn test_signed_tree_head() {
+    let values = (0..5u8).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec_with_profile(DIGEST, Profile::Hardened, values);
+    let key_pair = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+    let other_key_pair = Ed25519KeyPair::from_seed_unchecked(&[8; 32]).unwrap();
+
+    let head = TreeHead::from_tree("batch".to_string(), &tree, 1_700_000_000);
+    assert_eq!(head.size, 5);
+    assert_eq!(&head.root_hash, tree.root_hash());
+
+    let signed = head.clone().sign(&key_pair);
+    assert!(signed.verify());
+    assert!(signed.is_signed_by(key_pair.public_key().as_ref()));
+    assert!(!signed.is_signed_by(other_key_pair.public_key().as_ref()));
+
+    let serialized = serde_json::to_string(&signed).expect("serialize head");
+    let deserialized = serde_json::from_str(&serialized).expect("deserialize head");
+    assert_eq!(signed, deserialized);
+
+    let tampered_heads = [
+        TreeHead {
+            batch_id: "other".to_string(),
+            ..head.clone()
+        },
+        TreeHead {
+            algorithm: &ring::digest::SHA256,
+            ..head.clone()
+        },
+        TreeHead {
+            size: 6,
+            ..head.clone()
+        },
+        TreeHead {
+            root_hash: tree.root_hash()[1..].to_vec(),
+            ..head.clone()
+        },
+        TreeHead {
+            timestamp: 1_700_000_001,
+            ..head.clone()
+        },
+    ];
+    for tampered_head in tampered_heads {
+        let mut tampered = signed.clone();
+        tampered.head = tampered_head;
+        assert!(!tampered.verify());
+    }
+
+    let mut resigned = signed;
+    resigned.public_key = other_key_pair.public_key().as_ref().to_vec();
+    assert!(!resigned.verify());
+
+    let proof = tree.gen_nth_proof(2).unwrap();
+    assert!(head.is_for_proof(&proof));
+    let other_tree = MerkleTree::from_vec_with_profile(DIGEST, Profile::Hardened, vec![vec![0]]);
+    assert!(!head.is_for_proof(&other_tree.gen_nth_proof(0).unwrap()));
+}
+
+#[test]
+fn test_consistency_proofs() {
+    for &profile in &[Profile::Legacy, Profile::Hardened] {
+        let values = (0..20u8).map(|x| vec![x]).collect::<Vec<_>>();
+        let trees = (0..=values.len())
+            .map(|n| MerkleTree::from_vec_with_profile(DIGEST, profile, values[..n].to_vec()))
+            .collect::<Vec<_>>();
+
+        for new_tree in &trees {
+            assert!(new_tree
+                .gen_consistency_proof(new_tree.count() + 1)
+                .is_none());
+
+            for old_tree in &trees[..=new_tree.count()] {
+                let proof = new_tree.gen_consistency_proof(old_tree.count()).unwrap();
+                assert_eq!(proof.old_size, old_tree.count());
+                assert_eq!(proof.new_size, new_tree.count());
+                assert!(proof.validate(old_tree.root_hash(), new_tree.root_hash()));
+
+                // A tree that was not a prefix, or other sizes, are detected.
+                let mut forked = values[..old_tree.count()].to_vec();
+                if let Some(last) = forked.last_mut() {
+                    last.push(0);
+                    let forked = MerkleTree::from_vec_with_profile(DIGEST, profile, forked);
+                    assert!(!proof.validate(forked.root_hash(), new_tree.root_hash()));
+                }
+                if old_tree.count() < new_tree.count() {
+                    assert!(!proof.validate(new_tree.root_hash(), old_tree.root_hash()));
+                }
+                // Only hardened roots commit to the size of their tree, and the empty
+                // tree is a prefix of any tree.
+                if profile == Profile::Hardened && old_tree.count() > 0 {
+                    let mut resized = proof.clone();
+                    resized.new_size += 1;
+                    assert!(!resized.validate(old_tree.root_hash(), new_tree.root_hash()));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_consistency_proof_matches_rfc_9162() {
+    // The example of RFC 9162, section 2.1.5, with leaves d0 to d6.
+    let leaves = (0..7u8).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(DIGEST, leaves.clone());
+    let hash = |range: std::ops::Range<usize>| {
+        MerkleTree::from_vec(DIGEST, leaves[range].to_vec())
+            .root_hash()
+            .clone()
+    };
+
+    let proof = |old_count| tree.gen_consistency_proof(old_count).unwrap().hashes;
+    // The consistency proof between hash0 and hash is PROOF(3, D[7]) = [c, d, g, l]...
+    assert_eq!(
+        proof(3),
+        vec![hash(2..3), hash(3..4), hash(0..2), hash(4..7)]
+    );
+    // ...between hash1 and hash is PROOF(4, D[7]) = [l]...
+    assert_eq!(proof(4), vec![hash(4..7)]);
+    // ...and between hash2 and hash is PROOF(6, D[7]) = [i, j, k].
+    assert_eq!(proof(6), vec![hash(4..6), hash(6..7), hash(0..4)]);
+}
+
+#[test]
+fn test_log() {
+    let mut log = Log::new(DIGEST, Profile::Hardened);
+    let mut heads = vec![log.head()];
+    for i in 0..6u8 {
+        let batch_id = format!("batch{}", i % 4);
+        assert_eq!(log.append(batch_id, vec![i; 64], i as u64), i as usize);
+        heads.push(log.head());
+    }
+    assert_eq!(log.size(), 6);
+    assert_eq!(log.find("batch1", 6), Some(5));
+    assert_eq!(log.find("batch1", 5), Some(1));
+    assert_eq!(log.find("batch2", 6), Some(2));
+    assert_eq!(log.find("batch2", 2), None);
+    assert_eq!(log.find("batch4", 6), None);
+    assert_eq!(log.find("batch1", 7), None);
+
+    for (old_size, old_head) in heads.iter().enumerate() {
+        for (new_size, new_head) in heads.iter().enumerate().skip(old_size) {
+            let proof = log.consistency_proof(old_size, new_size).unwrap();
+            assert!(old_head.is_consistent_with(new_head, &proof));
+            if old_size > 0 && old_size < new_size {
+                assert!(!new_head.is_consistent_with(old_head, &proof));
+            }
+
+            for index in 0..new_size {
+                let proof = log.inclusion_proof(index, new_size).unwrap();
+                assert!(new_head.is_for_proof(&proof));
+                assert!(proof.validate(&new_head.root_hash));
+                assert!(proof.is_for_value());
+                assert_eq!(&proof.value, &log.entries()[index]);
+            }
+            assert!(log.inclusion_proof(new_size, new_size).is_none());
+        }
+    }
+    assert!(log.consistency_proof(0, 7).is_none());
+
+    // A log is restored from its entries only if they are chained.
+    let restored = Log::from_entries(DIGEST, Profile::Hardened, log.entries().to_vec()).unwrap();
+    assert_eq!(restored.head(), log.head());
+
+    let mut entries = log.entries().to_vec();
+    entries.remove(2);
+    assert!(Log::from_entries(DIGEST, Profile::Hardened, entries).is_err());
+    let mut entries = log.entries().to_vec();
+    entries[3].root_hash[0] ^= 1;
+    assert!(Log::from_entries(DIGEST, Profile::Hardened, entries).is_err());
+}
+
+#[test]
+fn test_log_matches_tree() {
+    for profile in [Profile::Legacy, Profile::Hardened] {
+        let mut log = Log::new(DIGEST, profile);
+        for i in 0..=33u8 {
+            let tree = MerkleTree::from_vec_with_profile(DIGEST, profile, log.entries().to_vec());
+            let head = log.head();
+            assert_eq!(head.size, tree.count() as u64);
+            assert_eq!(&head.root_hash, tree.root_hash());
+
+            // Proofs from the hashes kept by the log are those of the whole tree.
+            let size = log.size();
+            for index in 0..size {
+                assert_eq!(log.inclusion_proof(index, size), tree.gen_nth_proof(index));
+            }
+            for old_size in 0..=size {
+                assert_eq!(
+                    log.consistency_proof(old_size, size),
+                    tree.gen_consistency_proof(old_size)
+                );
+            }
+            log.append(format!("batch{}", i), vec![i; 64], i as u64);
+        }
+    }
+}
+
+#[test]
+fn test_log_truncate() {
+    let mut log = Log::new(DIGEST, Profile::Hardened);
+    let mut heads = vec![log.head()];
+    for i in 0..7u8 {
+        log.append(format!("batch{}", i), vec![i; 64], i as u64);
+        heads.push(log.head());
+    }
+    for size in (0..7).rev() {
+        log.truncate(size);
+        assert_eq!(log.head(), heads[size]);
+        assert_eq!(log.size(), size);
+    }
+    log.append("batch0".to_string(), vec![0; 64], 0);
+    assert_eq!(log.head(), heads[1]);
+}
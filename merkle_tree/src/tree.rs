@@ -1,7 +1,9 @@
-use crate::hashing::{Hashable, Hashing};
-use ring::digest::{Algorithm, Digest};
+use crate::hashing::{Hashable, Hashing, Profile, TreeHasher};
+use crate::proof::{digests_match, Conjecture, Side};
+use ring::digest::Algorithm;
+use serde_derive::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum BinaryTree<T> {
     Empty {
         hash: Vec<u8>,
@@ -18,27 +20,173 @@ pub enum BinaryTree<T> {
 }
 
 impl<T> BinaryTree<T> {
-    pub fn empty(hash: Digest) -> Self {
-        BinaryTree::Empty {
-            hash: hash.as_ref().into(),
-        }
+    pub fn empty(hash: Vec<u8>) -> Self {
+        BinaryTree::Empty { hash }
     }
 
-    pub fn new(hash: Digest, value: T) -> Self {
-        BinaryTree::Leaf {
-            hash: hash.as_ref().into(),
-            value,
-        }
+    pub fn new(hash: Vec<u8>, value: T) -> Self {
+        BinaryTree::Leaf { hash, value }
     }
 
-    pub fn new_leaf(algo: &'static Algorithm, value: T) -> BinaryTree<T>
+    pub fn new_leaf<H: TreeHasher>(hasher: &H, value: T) -> BinaryTree<T>
     where
         T: Hashable,
     {
-        let hash = algo.hash_leaf(&value);
+        let hash = hasher.leaf_hash(&value);
         BinaryTree::new(hash, value)
     }
 
+    /// Attempts to generate a proof that the a value with hash `needle` is a
+    /// member of this tree.
+    pub fn conjecture(&self, needle: &[u8]) -> Option<Conjecture> {
+        match *self {
+            BinaryTree::Empty { .. } => None,
+
+            BinaryTree::Leaf { ref hash, .. } => Self::new_leaf_proof(hash, needle),
+
+            BinaryTree::Node {
+                ref hash,
+                ref left,
+                ref right,
+            } => Self::new_tree_proof(hash, needle, left, right),
+        }
+    }
+
+    /// Tries to generate a proof that the i-th leaf is a member of this tree.
+    /// `count` must be equal to the number of leaves in this tree.
+    /// Returns the new `Conjecture` and the i`-th value.
+    /// `None` is returned in case `idx >= count`.
+    pub fn conjecture_by_index(&self, i: usize, count: usize) -> Option<(Conjecture, &T)> {
+        if i >= count {
+            return None;
+        }
+        match *self {
+            BinaryTree::Empty { .. } => None,
+            BinaryTree::Leaf {
+                ref hash,
+                ref value,
+                ..
+            } => {
+                if count != 1 {
+                    return None;
+                }
+                let conjecture = Conjecture {
+                    node_hash: hash.clone(),
+                    sibling_hash: None,
+                    sub_conjecture: None,
+                };
+                Some((conjecture, value))
+            }
+            BinaryTree::Node {
+                ref hash,
+                ref left,
+                ref right,
+            } => {
+                let left_count = count.next_power_of_two() / 2;
+                let (sub_conjecture_val, sibling_hash);
+                if i < left_count {
+                    sub_conjecture_val = left.conjecture_by_index(i, left_count);
+                    sibling_hash = Side::Right(right.hash().clone());
+                } else {
+                    sub_conjecture_val =
+                        right.conjecture_by_index(i - left_count, count - left_count);
+                    sibling_hash = Side::Left(left.hash().clone());
+                }
+                sub_conjecture_val.map(|(sub_conjecture, value)| {
+                    let conjecture = Conjecture {
+                        node_hash: hash.clone(),
+                        sibling_hash: Some(sibling_hash),
+                        sub_conjecture: Some(Box::new(sub_conjecture)),
+                    };
+                    (conjecture, value)
+                })
+            }
+        }
+    }
+
+    /// Generates a proof for every leaf of this tree in a single traversal.
+    /// Returns the `Conjecture`s paired with their values, ordered by leaf index.
+    pub fn all_conjectures(&self) -> Vec<(Conjecture, &T)> {
+        match *self {
+            BinaryTree::Empty { .. } => Vec::new(),
+            BinaryTree::Leaf {
+                ref hash,
+                ref value,
+            } => {
+                let conjecture = Conjecture {
+                    node_hash: hash.clone(),
+                    sibling_hash: None,
+                    sub_conjecture: None,
+                };
+                vec![(conjecture, value)]
+            }
+            BinaryTree::Node {
+                ref hash,
+                ref left,
+                ref right,
+            } => {
+                let wrap = |sibling_hash: Side<Vec<u8>>| {
+                    move |(sub_conjecture, value)| {
+                        let conjecture = Conjecture {
+                            node_hash: hash.clone(),
+                            sibling_hash: Some(sibling_hash.clone()),
+                            sub_conjecture: Some(Box::new(sub_conjecture)),
+                        };
+                        (conjecture, value)
+                    }
+                };
+                let from_left = left
+                    .all_conjectures()
+                    .into_iter()
+                    .map(wrap(Side::Right(right.hash().clone())));
+                let from_right = right
+                    .all_conjectures()
+                    .into_iter()
+                    .map(wrap(Side::Left(left.hash().clone())));
+                from_left.chain(from_right).collect()
+            }
+        }
+    }
+
+    fn new_leaf_proof(hash: &[u8], needle: &[u8]) -> Option<Conjecture> {
+        if digests_match(hash, needle) {
+            Some(Conjecture {
+                node_hash: hash.into(),
+                sibling_hash: None,
+                sub_conjecture: None,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn new_tree_proof(
+        hash: &[u8],
+        needle: &[u8],
+        left: &BinaryTree<T>,
+        right: &BinaryTree<T>,
+    ) -> Option<Conjecture> {
+        left.conjecture(needle)
+            .map(|conjecture| {
+                let right_hash = right.hash().clone();
+                let sub_conjecture = Some(Side::Right(right_hash));
+                (conjecture, sub_conjecture)
+            })
+            .or_else(|| {
+                let sub_conjecture = right.conjecture(needle);
+                sub_conjecture.map(|conjecture| {
+                    let left_hash = left.hash().clone();
+                    let sub_conjecture = Some(Side::Left(left_hash));
+                    (conjecture, sub_conjecture)
+                })
+            })
+            .map(|(sub_conjecture, sibling_hash)| Conjecture {
+                node_hash: hash.into(),
+                sibling_hash,
+                sub_conjecture: Some(Box::new(sub_conjecture)),
+            })
+    }
+
     pub fn hash(&self) -> &Vec<u8> {
         match *self {
             BinaryTree::Empty { ref hash } => hash,
@@ -46,4 +194,132 @@ impl<T> BinaryTree<T> {
             BinaryTree::Node { ref hash, .. } => hash,
         }
     }
+
+    /// Pushes onto `indices` the index of every leaf whose hash equals `needle`.
+    /// `next_index` is the index of the first leaf of this subtree.
+    pub fn collect_leaf_indices(
+        &self,
+        needle: &[u8],
+        next_index: &mut usize,
+        indices: &mut Vec<usize>,
+    ) {
+        match *self {
+            BinaryTree::Empty { .. } => {}
+            BinaryTree::Leaf { ref hash, .. } => {
+                if digests_match(hash, needle) {
+                    indices.push(*next_index);
+                }
+                *next_index += 1;
+            }
+            BinaryTree::Node {
+                ref left,
+                ref right,
+                ..
+            } => {
+                left.collect_leaf_indices(needle, next_index, indices);
+                right.collect_leaf_indices(needle, next_index, indices);
+            }
+        }
+    }
+
+    /// Checks that every internal hash of this tree is the hash of its children,
+    /// and that the tree has the shape `MerkleTree::from_vec` would give it.
+    /// Leaf hashes are taken as given, and so are all hashes of a `keyed` tree,
+    /// which can only be recomputed with the key.
+    /// Returns the number of leaves and the height of the tree.
+    pub fn verify(
+        &self,
+        algorithm: &'static Algorithm,
+        profile: Profile,
+        keyed: bool,
+    ) -> Result<(usize, usize), String> {
+        match *self {
+            BinaryTree::Empty { ref hash } => {
+                if hash.len() != algorithm.output_len
+                    || !keyed && !digests_match(hash, profile.hash_empty(algorithm).as_ref())
+                {
+                    return Err("empty tree hash mismatch".to_string());
+                }
+                Ok((0, 0))
+            }
+            BinaryTree::Leaf { ref hash, .. } => {
+                if hash.len() != algorithm.output_len {
+                    return Err("leaf hash has wrong length".to_string());
+                }
+                Ok((1, 0))
+            }
+            BinaryTree::Node {
+                ref hash,
+                ref left,
+                ref right,
+            } => {
+                let (left_count, left_height) = left.verify(algorithm, profile, keyed)?;
+                let (right_count, right_height) = right.verify(algorithm, profile, keyed)?;
+                if left_count == 0 || right_count == 0 {
+                    return Err("empty subtree below a node".to_string());
+                }
+
+                let count = left_count + right_count;
+                if left_count != count.next_power_of_two() / 2 {
+                    return Err("unbalanced node".to_string());
+                }
+
+                if hash.len() != algorithm.output_len
+                    || !keyed
+                        && !digests_match(
+                            hash,
+                            algorithm.hash_nodes(left.hash(), right.hash()).as_ref(),
+                        )
+                {
+                    return Err("node hash mismatch".to_string());
+                }
+
+                Ok((count, 1 + left_height.max(right_height)))
+            }
+        }
+    }
+
+    /// Pushes to `hashes` the consistency path from the first `old_count` leaves
+    /// of this tree to the whole tree, which has `count` leaves, as in `SUBPROOF`
+    /// of RFC 9162, section 2.1.4.1.
+    ///
+    /// `complete` is whether this tree is the leftmost subtree of the whole tree.
+    /// Its hash is then left out, unless `keep_complete` is set.
+    pub fn consistency_path(
+        &self,
+        old_count: usize,
+        count: usize,
+        complete: bool,
+        keep_complete: bool,
+        hashes: &mut Vec<Vec<u8>>,
+    ) {
+        match *self {
+            _ if old_count == count => {
+                if !complete || keep_complete {
+                    hashes.push(self.hash().clone());
+                }
+            }
+            BinaryTree::Node {
+                ref left,
+                ref right,
+                ..
+            } => {
+                let left_count = count.next_power_of_two() / 2;
+                if old_count <= left_count {
+                    left.consistency_path(old_count, left_count, complete, keep_complete, hashes);
+                    hashes.push(right.hash().clone());
+                } else {
+                    right.consistency_path(
+                        old_count - left_count,
+                        count - left_count,
+                        false,
+                        keep_complete,
+                        hashes,
+                    );
+                    hashes.push(left.hash().clone());
+                }
+            }
+            BinaryTree::Empty { .. } | BinaryTree::Leaf { .. } => {}
+        }
+    }
 }
@@ -0,0 +1,204 @@
+//! Iterators and a visitor over the nodes of a `MerkleTree`.
+
+use crate::tree::BinaryTree;
+
+/// A leaf of a `MerkleTree`, as yielded by `MerkleTree::leaves`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Leaf<'a, T> {
+    /// The index of the leaf, from left to right
+    pub index: usize,
+    /// The leaf hash of the value
+    pub hash: &'a [u8],
+    /// The value stored in the leaf
+    pub value: &'a T,
+}
+
+// Derived `Clone` and `Copy` would require `T: Clone`.
+impl<'a, T> Clone for Leaf<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for Leaf<'a, T> {}
+
+/// An internal node of a `MerkleTree`, as passed to a `Visitor`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Node<'a> {
+    /// The hash of the node
+    pub hash: &'a [u8],
+    /// The hash of the left child
+    pub left_hash: &'a [u8],
+    /// The hash of the right child
+    pub right_hash: &'a [u8],
+    /// The distance from the root, which is at depth 0
+    pub depth: usize,
+    /// The index of the first leaf below this node
+    pub first_leaf: usize,
+    /// The number of leaves below this node
+    pub leaf_count: usize,
+}
+
+/// Visits the nodes of a `MerkleTree` in depth-first order, left to right.
+/// See `MerkleTree::walk`.
+///
+/// Every method does nothing by default, so that implementors only
+/// need to override the ones they are interested in.
+pub trait Visitor<T> {
+    /// Called on an internal node, before its children are visited.
+    fn visit_node(&mut self, _node: Node<'_>) {}
+
+    /// Called on an internal node, after its children were visited.
+    fn leave_node(&mut self, _node: Node<'_>) {}
+
+    /// Called on every leaf, by increasing index.
+    /// `depth` is the distance from the root.
+    fn visit_leaf(&mut self, _leaf: Leaf<'_, T>, _depth: usize) {}
+
+    /// Called on the root of an empty tree.
+    fn visit_empty(&mut self, _hash: &[u8]) {}
+}
+
+/// An iterator over the leaves of a `MerkleTree`, by increasing index.
+/// See `MerkleTree::leaves`.
+pub struct Leaves<'a, T> {
+    stack: Vec<&'a BinaryTree<T>>,
+    next_index: usize,
+}
+
+impl<'a, T> Leaves<'a, T> {
+    pub(crate) fn new(root: &'a BinaryTree<T>) -> Self {
+        Leaves {
+            stack: vec![root],
+            next_index: 0,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Leaves<'a, T> {
+    type Item = Leaf<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match *node {
+                BinaryTree::Empty { .. } => {}
+                BinaryTree::Leaf {
+                    ref hash,
+                    ref value,
+                } => {
+                    let leaf = Leaf {
+                        index: self.next_index,
+                        hash,
+                        value,
+                    };
+                    self.next_index += 1;
+                    return Some(leaf);
+                }
+                BinaryTree::Node {
+                    ref left,
+                    ref right,
+                    ..
+                } => {
+                    self.stack.push(right);
+                    self.stack.push(left);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Walks `node`, the first leaf of which has index `first_leaf`
+/// and which has `leaf_count` leaves, calling `visitor` on it and its descendants.
+pub(crate) fn walk<T, V>(
+    node: &BinaryTree<T>,
+    depth: usize,
+    first_leaf: usize,
+    leaf_count: usize,
+    visitor: &mut V,
+) where
+    V: Visitor<T> + ?Sized,
+{
+    match *node {
+        BinaryTree::Empty { ref hash } => visitor.visit_empty(hash),
+        BinaryTree::Leaf {
+            ref hash,
+            ref value,
+        } => {
+            let leaf = Leaf {
+                index: first_leaf,
+                hash,
+                value,
+            };
+            visitor.visit_leaf(leaf, depth);
+        }
+        BinaryTree::Node {
+            ref hash,
+            ref left,
+            ref right,
+        } => {
+            let node = Node {
+                hash,
+                left_hash: left.hash(),
+                right_hash: right.hash(),
+                depth,
+                first_leaf,
+                leaf_count,
+            };
+            let left_count = leaf_count.next_power_of_two() / 2;
+
+            visitor.visit_node(node);
+            walk(left, depth + 1, first_leaf, left_count, visitor);
+            walk(
+                right,
+                depth + 1,
+                first_leaf + left_count,
+                leaf_count - left_count,
+                visitor,
+            );
+            visitor.leave_node(node);
+        }
+    }
+}
+
+/// Returns the hashes of every level of the tree rooted at `root`, which has `count` leaves,
+/// from the leaves up to the root.
+///
+/// Level `k` holds the hashes of the subtrees covering `2^k` consecutive leaves, the last one
+/// possibly covering fewer. A node without a sibling is promoted, and so appears on several levels.
+pub(crate) fn levels<T>(root: &BinaryTree<T>, count: usize, height: usize) -> Vec<Vec<&[u8]>> {
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let mut levels = Vec::with_capacity(height + 1);
+    let mut level = vec![(root, count)];
+
+    for k in (0..=height).rev() {
+        levels.push(
+            level
+                .iter()
+                .map(|(node, _)| node.hash().as_slice())
+                .collect(),
+        );
+        if k == 0 {
+            break;
+        }
+
+        let half = 1 << (k - 1);
+        level = level
+            .into_iter()
+            .flat_map(|(node, count)| match *node {
+                BinaryTree::Node {
+                    ref left,
+                    ref right,
+                    ..
+                } if count > half => vec![(&**left, half), (&**right, count - half)],
+                _ => vec![(node, count)],
+            })
+            .collect();
+    }
+
+    levels.reverse();
+    levels
+}
@@ -0,0 +1,229 @@
+//! Python bindings for the `merkle_tree` crate.
+//!
+//! Trees are built over `FileLeaf` values, as the client builds them for a batch.
+//! Proofs are exchanged as JSON strings, in the format the server sends
+//! in the `proof` header, and hashes as `bytes`.
+
+use merkle_tree::{
+    hashing::{algorithm_by_name, Hashing, Profile},
+    leaf::FileLeaf,
+    merkletree::MerkleTree,
+    proof::Proof,
+};
+use pyo3::{
+    exceptions::{PyIndexError, PyValueError},
+    prelude::*,
+    types::PyBytes,
+};
+use ring::digest::{digest, Algorithm};
+
+fn algorithm(name: &str) -> PyResult<&'static Algorithm> {
+    algorithm_by_name(name)
+        .ok_or_else(|| PyValueError::new_err(format!("unknown hash algorithm: {}", name)))
+}
+
+fn profile(name: &str) -> PyResult<Profile> {
+    match name {
+        "Legacy" => Ok(Profile::Legacy),
+        "Hardened" => Ok(Profile::Hardened),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown hashing profile: {}",
+            name
+        ))),
+    }
+}
+
+/// A file of a batch: its path, metadata and content hash.
+#[pyclass(name = "FileLeaf")]
+#[derive(Clone)]
+struct PyFileLeaf {
+    leaf: FileLeaf,
+}
+
+#[pymethods]
+impl PyFileLeaf {
+    /// Describes the file at `path`, relative to its batch, with the given `content`.
+    #[new]
+    #[pyo3(signature = (path, content, mtime = None, mode = None, algorithm = "SHA512"))]
+    fn new(
+        path: String,
+        content: &[u8],
+        mtime: Option<u64>,
+        mode: Option<u32>,
+        algorithm: &str,
+    ) -> PyResult<Self> {
+        if !FileLeaf::is_safe_path(&path) {
+            return Err(PyValueError::new_err(format!("unsafe path: {}", path)));
+        }
+        let content_hash = digest(self::algorithm(algorithm)?, content);
+
+        Ok(PyFileLeaf {
+            leaf: FileLeaf {
+                path,
+                size: content.len() as u64,
+                mtime,
+                mode,
+                content_hash: content_hash.as_ref().to_vec(),
+            },
+        })
+    }
+
+    #[getter]
+    fn path(&self) -> String {
+        self.leaf.path.clone()
+    }
+
+    #[getter]
+    fn size(&self) -> u64 {
+        self.leaf.size
+    }
+
+    #[getter]
+    fn mtime(&self) -> Option<u64> {
+        self.leaf.mtime
+    }
+
+    #[getter]
+    fn mode(&self) -> Option<u32> {
+        self.leaf.mode
+    }
+
+    #[getter]
+    fn content_hash<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.leaf.content_hash)
+    }
+
+    /// Computes the leaf hash of the file.
+    #[pyo3(signature = (algorithm = "SHA512"))]
+    fn leaf_hash<'py>(&self, py: Python<'py>, algorithm: &str) -> PyResult<&'py PyBytes> {
+        let leaf_hash = self::algorithm(algorithm)?.hash_leaf(&self.leaf);
+        Ok(PyBytes::new(py, leaf_hash.as_ref()))
+    }
+}
+
+/// A Merkle tree over the given files, sorted by path as the client sorts a batch.
+/// The default profile is that of the server, so that the root of the same files
+/// matches the root of their batch.
+#[pyclass(name = "MerkleTree")]
+struct PyMerkleTree {
+    tree: MerkleTree<FileLeaf>,
+}
+
+#[pymethods]
+impl PyMerkleTree {
+    #[new]
+    #[pyo3(signature = (leaves, algorithm = "SHA512", profile = "Hardened"))]
+    fn new(leaves: Vec<PyFileLeaf>, algorithm: &str, profile: &str) -> PyResult<Self> {
+        let algorithm = self::algorithm(algorithm)?;
+        let profile = self::profile(profile)?;
+        let mut leaves: Vec<FileLeaf> = leaves.into_iter().map(|leaf| leaf.leaf).collect();
+        leaves.sort_by(FileLeaf::cmp_canonical);
+        if !FileLeaf::is_canonical_order(&leaves) {
+            return Err(PyValueError::new_err("two files have the same path"));
+        }
+
+        Ok(PyMerkleTree {
+            tree: MerkleTree::from_vec_with_profile(algorithm, profile, leaves),
+        })
+    }
+
+    /// The root hash of the tree.
+    #[getter]
+    fn root_hash<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, self.tree.root_hash())
+    }
+
+    /// The name of the hashing algorithm, e.g. `"SHA512"`.
+    #[getter]
+    fn algorithm(&self) -> String {
+        format!("{:?}", self.tree.algorithm)
+    }
+
+    /// The name of the hashing profile, `"Hardened"` or `"Legacy"`.
+    #[getter]
+    fn profile(&self) -> String {
+        format!("{:?}", self.tree.profile())
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.tree.height()
+    }
+
+    #[getter]
+    fn count(&self) -> usize {
+        self.tree.count()
+    }
+
+    fn __len__(&self) -> usize {
+        self.tree.count()
+    }
+
+    /// Returns the JSON serialized inclusion proof of the `n`-th leaf.
+    fn gen_nth_proof(&self, n: usize) -> PyResult<String> {
+        let proof = self
+            .tree
+            .gen_nth_proof(n)
+            .ok_or_else(|| PyIndexError::new_err("leaf index out of range"))?;
+
+        serde_json::to_string(&proof).map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+}
+
+fn parse_proof(proof: &str) -> PyResult<Proof<FileLeaf>> {
+    serde_json::from_str(proof).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+/// Computes the digest of `content`, as found in the `content_hash` of a file.
+#[pyfunction]
+#[pyo3(signature = (content, algorithm = "SHA512"))]
+fn hash_content<'py>(py: Python<'py>, content: &[u8], algorithm: &str) -> PyResult<&'py PyBytes> {
+    let content_hash = digest(self::algorithm(algorithm)?, content);
+    Ok(PyBytes::new(py, content_hash.as_ref()))
+}
+
+/// Checks that the JSON serialized `proof` is valid for `root_hash` and is about
+/// its file leaf, and, if given, that `content` has the size and digest of the file.
+#[pyfunction]
+#[pyo3(signature = (proof, root_hash, content = None))]
+fn verify_proof(proof: &str, root_hash: &[u8], content: Option<&[u8]>) -> PyResult<bool> {
+    let proof = parse_proof(proof)?;
+
+    let content_matches = content
+        .map(|content| proof.value.matches_content(proof.algorithm, content))
+        .unwrap_or(true);
+
+    Ok(content_matches && proof.validate(root_hash) && proof.is_for_value())
+}
+
+/// Returns the file leaf the JSON serialized `proof` is about.
+/// It is only trustworthy once the proof is verified.
+#[pyfunction]
+fn proof_leaf(proof: &str) -> PyResult<PyFileLeaf> {
+    Ok(PyFileLeaf {
+        leaf: parse_proof(proof)?.value,
+    })
+}
+
+/// Returns the index of the leaf the JSON serialized `proof` is about,
+/// in a tree of `count` leaves.
+#[pyfunction]
+fn proof_index(proof: &str, count: usize) -> PyResult<usize> {
+    let proof = parse_proof(proof)?;
+
+    if !proof.validate(&proof.root_hash) {
+        return Err(PyValueError::new_err("malformed proof"));
+    }
+    Ok(proof.index(count))
+}
+
+#[pymodule]
+fn mtfs_merkle(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyFileLeaf>()?;
+    m.add_class::<PyMerkleTree>()?;
+    m.add_function(wrap_pyfunction!(hash_content, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_proof, m)?)?;
+    m.add_function(wrap_pyfunction!(proof_leaf, m)?)?;
+    m.add_function(wrap_pyfunction!(proof_index, m)?)?;
+    Ok(())
+}
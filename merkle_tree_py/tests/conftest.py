@@ -0,0 +1,43 @@
+"""Builds the extension module with cargo and makes it importable.
+
+Set MTFS_MERKLE_SKIP_BUILD=1 to test a module that is already installed,
+e.g. with `maturin develop`.
+"""
+
+import os
+import pathlib
+import shutil
+import subprocess
+import sys
+import sysconfig
+
+CRATE_DIR = pathlib.Path(__file__).resolve().parent.parent
+WORKSPACE_DIR = CRATE_DIR.parent
+
+
+def _build_module(out_dir: pathlib.Path) -> None:
+    subprocess.run(
+        ["cargo", "build", "--package", "merkle_tree_py"],
+        cwd=WORKSPACE_DIR,
+        check=True,
+    )
+    target_dir = pathlib.Path(
+        os.environ.get("CARGO_TARGET_DIR", WORKSPACE_DIR / "target")
+    )
+    if sys.platform == "darwin":
+        library = "libmtfs_merkle.dylib"
+    elif sys.platform == "win32":
+        library = "mtfs_merkle.dll"
+    else:
+        library = "libmtfs_merkle.so"
+    suffix = sysconfig.get_config_var("EXT_SUFFIX")
+    shutil.copy(target_dir / "debug" / library, out_dir / f"mtfs_merkle{suffix}")
+
+
+def pytest_configure(config):
+    if os.environ.get("MTFS_MERKLE_SKIP_BUILD"):
+        return
+    out_dir = CRATE_DIR / ".pytest_cache" / "mtfs_merkle"
+    out_dir.mkdir(parents=True, exist_ok=True)
+    _build_module(out_dir)
+    sys.path.insert(0, str(out_dir))
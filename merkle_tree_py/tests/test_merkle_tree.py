@@ -0,0 +1,152 @@
+import json
+
+import pytest
+
+import mtfs_merkle
+
+
+CONTENTS = [bytes([i]) * 100 for i in range(1, 11)]
+LEAVES = [
+    mtfs_merkle.FileLeaf(f"file{i:02}.bin", content, mtime=1_700_000_000, mode=0o644)
+    for i, content in enumerate(CONTENTS)
+]
+
+
+def flip(data: bytes, at: int = 0) -> bytes:
+    data = bytearray(data)
+    data[at] ^= 1
+    return bytes(data)
+
+
+def test_tree_shape():
+    tree = mtfs_merkle.MerkleTree(LEAVES[:3])
+
+    assert tree.count == 3
+    assert len(tree) == 3
+    assert tree.height == 2
+    assert tree.algorithm == "SHA512"
+    assert tree.profile == "Hardened"
+    assert len(tree.root_hash) == 64
+
+
+def test_file_leaf():
+    leaf = LEAVES[3]
+
+    assert leaf.path == "file03.bin"
+    assert leaf.size == 100
+    assert leaf.mtime == 1_700_000_000
+    assert leaf.mode == 0o644
+    assert len(leaf.content_hash) == 64
+    assert mtfs_merkle.FileLeaf("file03.bin", CONTENTS[3]).mtime is None
+
+
+def test_root_depends_on_leaves_and_algorithm():
+    tree = mtfs_merkle.MerkleTree(LEAVES)
+
+    assert mtfs_merkle.MerkleTree(LEAVES).root_hash == tree.root_hash
+    assert mtfs_merkle.MerkleTree(LEAVES[::-1]).root_hash == tree.root_hash
+    assert mtfs_merkle.MerkleTree(LEAVES[1:]).root_hash != tree.root_hash
+    assert mtfs_merkle.MerkleTree(LEAVES, profile="Legacy").root_hash != tree.root_hash
+    assert len(mtfs_merkle.MerkleTree(LEAVES, algorithm="SHA256").root_hash) == 32
+
+
+def test_empty_tree():
+    tree = mtfs_merkle.MerkleTree([])
+
+    assert tree.count == 0
+    with pytest.raises(IndexError):
+        tree.gen_nth_proof(0)
+
+
+@pytest.mark.parametrize("count", [1, 2, 3, 10])
+def test_nth_proof(count):
+    tree = mtfs_merkle.MerkleTree(LEAVES[:count])
+
+    for i in range(count):
+        proof = tree.gen_nth_proof(i)
+        assert mtfs_merkle.verify_proof(proof, tree.root_hash)
+        assert mtfs_merkle.verify_proof(proof, tree.root_hash, CONTENTS[i])
+        assert mtfs_merkle.proof_index(proof, tree.count) == i
+        assert mtfs_merkle.proof_leaf(proof).path == LEAVES[i].path
+
+    with pytest.raises(IndexError):
+        tree.gen_nth_proof(count)
+
+
+def test_proof_format_matches_client():
+    tree = mtfs_merkle.MerkleTree(LEAVES)
+    proof = json.loads(tree.gen_nth_proof(4))
+
+    assert proof["algorithm"] == "SHA512"
+    assert bytes(proof["root_hash"]) == tree.root_hash
+    assert proof["value"]["path"] == "file04.bin"
+    assert bytes(proof["value"]["content_hash"]) == LEAVES[4].content_hash
+    assert proof["profile"] == "Hardened"
+    assert proof["count"] == tree.count
+    assert set(proof["conjecture"]) == {"node_hash", "sibling_hash", "sub_conjecture"}
+
+
+def test_rejects_wrong_root_and_content():
+    tree = mtfs_merkle.MerkleTree(LEAVES)
+    proof = tree.gen_nth_proof(4)
+
+    assert not mtfs_merkle.verify_proof(proof, flip(tree.root_hash))
+    assert not mtfs_merkle.verify_proof(proof, tree.root_hash[:-1])
+    assert not mtfs_merkle.verify_proof(proof, tree.root_hash, CONTENTS[5])
+    assert not mtfs_merkle.verify_proof(proof, tree.root_hash, flip(CONTENTS[4]))
+    assert not mtfs_merkle.verify_proof(proof, tree.root_hash, CONTENTS[4][:-1])
+
+
+def test_rejects_tampered_proof():
+    tree = mtfs_merkle.MerkleTree(LEAVES)
+    proof = json.loads(tree.gen_nth_proof(4))
+
+    sibling = proof["conjecture"]["sibling_hash"]
+    side = next(iter(sibling))
+    sibling[side][0] ^= 1
+
+    assert not mtfs_merkle.verify_proof(json.dumps(proof), tree.root_hash)
+
+
+def test_rejects_tampered_value():
+    tree = mtfs_merkle.MerkleTree(LEAVES)
+    proof = json.loads(tree.gen_nth_proof(4))
+
+    # A proof for other content, whose path still leads to the root.
+    proof["value"]["content_hash"] = list(mtfs_merkle.hash_content(b"other"))
+    proof["value"]["size"] = 5
+
+    assert not mtfs_merkle.verify_proof(json.dumps(proof), tree.root_hash)
+    assert not mtfs_merkle.verify_proof(json.dumps(proof), tree.root_hash, b"other")
+
+
+def test_hash_content():
+    assert mtfs_merkle.hash_content(CONTENTS[0]) == LEAVES[0].content_hash
+    assert len(mtfs_merkle.hash_content(CONTENTS[0], "SHA256")) == 32
+
+
+def test_leaf_hash():
+    # Only the `Legacy` profile uses the hash of a single leaf as the root.
+    legacy = mtfs_merkle.MerkleTree(LEAVES[:1], profile="Legacy")
+    hardened = mtfs_merkle.MerkleTree(LEAVES[:1])
+
+    assert LEAVES[0].leaf_hash() == legacy.root_hash
+    assert LEAVES[0].leaf_hash() != hardened.root_hash
+    assert LEAVES[0].leaf_hash("SHA256") != legacy.root_hash
+
+
+def test_invalid_input():
+    with pytest.raises(ValueError):
+        mtfs_merkle.MerkleTree(LEAVES, algorithm="MD5")
+    with pytest.raises(ValueError):
+        mtfs_merkle.MerkleTree(LEAVES, profile="Other")
+    with pytest.raises(ValueError):
+        mtfs_merkle.MerkleTree(LEAVES + LEAVES[:1])
+    with pytest.raises(ValueError):
+        mtfs_merkle.FileLeaf("../escape", b"")
+    with pytest.raises(ValueError):
+        mtfs_merkle.hash_content(b"", "MD5")
+    with pytest.raises(ValueError):
+        mtfs_merkle.verify_proof("{}", b"")
+    with pytest.raises(ValueError):
+        mtfs_merkle.proof_leaf("{}")
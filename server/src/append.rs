@@ -0,0 +1,141 @@
+use crate::{
+    error::Error,
+    upload::{self, clean_up, commit, move_staged, receive_files, receive_manifest},
+    Batch, State,
+};
+use axum::{
+    extract::{
+        multipart::MultipartRejection,
+        rejection::{PathRejection, QueryRejection},
+        Multipart, Path, Query,
+    },
+    http::StatusCode,
+    Extension, Json,
+};
+use common::{AppendReceipt, BatchId};
+use merkle_tree::{leaf::FileLeaf, merkletree::MerkleTree, proof::digests_match};
+use serde_derive::Deserialize;
+use std::sync::Arc;
+use tracing::info;
+
+#[derive(Deserialize)]
+pub struct AppendParams {
+    /// The number of files of the batch as known to the client, if it is checked
+    old_size: Option<u64>,
+}
+
+/// Returns the batch if `files` can be appended to it, and it has `old_size` files
+/// according to the client.
+fn check_append<'a>(
+    batch: Option<&'a Batch>,
+    batch_id: &BatchId,
+    old_size: Option<u64>,
+    files: &[FileLeaf],
+) -> Result<&'a Batch, Error> {
+    let batch = batch.ok_or_else(|| Error::NotFound(format!("no such batch {}", batch_id)))?;
+    if batch.tree.is_keyed() {
+        return Err(Error::BadRequest(format!(
+            "batch {} is keyed and cannot be appended to; upload it again with --replace",
+            batch_id
+        )));
+    }
+    let count = batch.tree.count() as u64;
+    if old_size.map_or(false, |old_size| old_size != count) {
+        return Err(Error::Conflict(format!(
+            "batch {} has {} files, not {}",
+            batch_id,
+            count,
+            old_size.unwrap_or_default()
+        )));
+    }
+    if let Some(leaf) = batch
+        .tree
+        .leaves()
+        .find(|leaf| files.iter().any(|file| file.path == leaf.value.path))
+    {
+        return Err(Error::Conflict(format!(
+            "batch {} already has a file {}",
+            batch_id, leaf.value.path
+        )));
+    }
+    Ok(batch)
+}
+
+/// Receives files into the staging directory of the upload, and appends them
+/// to the batch once every file is received.
+pub async fn append(
+    state: Extension<Arc<State>>,
+    batch_id: BatchId,
+    old_size: Option<u64>,
+    multipart: Multipart,
+) -> Result<AppendReceipt, Error> {
+    let upload_id = upload::upload_id()?;
+    let result = receive(&state, &batch_id, old_size, &upload_id, multipart).await;
+    clean_up(&state, &batch_id, &upload_id, result.is_ok()).await;
+    result
+}
+
+async fn receive(
+    state: &State,
+    batch_id: &BatchId,
+    old_size: Option<u64>,
+    upload_id: &str,
+    mut multipart: Multipart,
+) -> Result<AppendReceipt, Error> {
+    let tree = receive_manifest(&mut multipart).await?;
+    if tree.is_keyed() {
+        return Err(Error::BadRequest(
+            "files cannot be appended with a keyed tree".to_string(),
+        ));
+    }
+    let files: Vec<FileLeaf> = tree.leaves().map(|leaf| leaf.value.clone()).collect();
+    let (mut leaves, old_root) = {
+        let batch_tree_map = state.batch_tree_map.lock().await;
+        let batch = check_append(batch_tree_map.get(batch_id), batch_id, old_size, &files)?;
+        let leaves: Vec<FileLeaf> = batch.tree.leaves().map(|leaf| leaf.value.clone()).collect();
+        (leaves, batch.tree.root_hash().clone())
+    };
+
+    let staged = receive_files(state, upload_id, &files, false, &mut multipart).await?;
+    let appended = move_staged(state, batch_id, upload_id, &files, &staged).await?;
+
+    // Appended files follow the files of the batch, so that the old tree is a prefix.
+    let old_count = leaves.len();
+    leaves.extend(files.iter().cloned());
+    let tree = MerkleTree::from_vec_with_profile(common::DIGEST, common::PROFILE, leaves);
+    let proof = tree
+        .gen_consistency_proof(old_count)
+        .ok_or_else(|| anyhow::Error::msg("no consistency proof for the appended tree"))?;
+    let receipt = upload::receipt(batch_id, &tree);
+
+    let mut batch_tree_map = state.batch_tree_map.lock().await;
+    // The batch may have changed since the manifest was received.
+    let batch = check_append(batch_tree_map.get(batch_id), batch_id, old_size, &files)?;
+    if !digests_match(batch.tree.root_hash(), &old_root) {
+        return Err(Error::Conflict(format!(
+            "batch {} changed while files were appended to it",
+            batch_id
+        )));
+    }
+    // Keys are taken under the lock, so that files deleted meanwhile stay deleted.
+    let mut keys = batch.keys.clone();
+    keys.extend(appended.into_iter().map(Some));
+    commit(state, &mut batch_tree_map, batch_id, Batch { tree, keys }).await?;
+
+    Ok(AppendReceipt { receipt, proof })
+}
+
+pub async fn append_handler(
+    state: Extension<Arc<State>>,
+    batch_id: Result<Path<BatchId>, PathRejection>,
+    params: Result<Query<AppendParams>, QueryRejection>,
+    multipart: Result<Multipart, MultipartRejection>,
+) -> Result<(StatusCode, Json<AppendReceipt>), Error> {
+    info!("About to append files to a batch");
+    let Path(batch_id) = batch_id.map_err(|err| Error::BadRequest(err.body_text()))?;
+    let Query(AppendParams { old_size }) =
+        params.map_err(|err| Error::BadRequest(err.body_text()))?;
+    let multipart = multipart.map_err(|err| Error::BadRequest(err.body_text()))?;
+    let receipt = append(state, batch_id, old_size, multipart).await?;
+    Ok((StatusCode::CREATED, Json(receipt)))
+}
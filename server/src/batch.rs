@@ -1,13 +1,17 @@
-use merkle_tree::merkletree::MerkleTree;
-use std::path::PathBuf;
+use merkle_tree::{leaf::FileLeaf, merkletree::MerkleTree};
 
 pub struct Batch {
-    pub(crate) tree: MerkleTree<Vec<u8>>,
-    pub(crate) paths: Vec<PathBuf>,
+    pub(crate) tree: MerkleTree<FileLeaf>,
+    /// The storage keys of the files, in the order of the leaves,
+    /// or `None` for the tombstones of deleted files, whose leaves are kept
+    pub(crate) keys: Vec<Option<String>>,
 }
 
 impl Batch {
-    pub(crate) fn new(tree: MerkleTree<Vec<u8>>, paths: Vec<PathBuf>) -> Batch {
-        Batch { tree, paths }
+    pub(crate) fn new(tree: MerkleTree<FileLeaf>, keys: Vec<String>) -> Batch {
+        Batch {
+            tree,
+            keys: keys.into_iter().map(Some).collect(),
+        }
     }
 }
@@ -0,0 +1,202 @@
+//! The durable catalog of batches and of the log of their roots,
+//! so that both survive restarts of the server.
+
+use crate::{storage::StorageBackend, Batch};
+use anyhow::Result;
+use common::BatchId;
+use merkle_tree::{
+    hashing::HashingReader,
+    leaf::FileLeaf,
+    log::{Log, LogEntry},
+    merkletree::MerkleTree,
+    proof::{algorithm_serde, digests_match},
+};
+use ring::digest::{Algorithm, Context};
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
+use tokio::io::AsyncReadExt;
+
+const BATCHES_PREFIX: &str = ".catalog/batches/";
+const LOG_PREFIX: &str = ".catalog/log/";
+/// Where the whole log was kept, before its entries were stored one by one
+const LEGACY_LOG_KEY: &str = ".catalog/log.json";
+
+/// What the catalog keeps of a batch: its tree, whose leaves are the manifest,
+/// and the storage key of each of its files, in the order of the leaves,
+/// or `null` for deleted files.
+#[derive(Serialize, Deserialize)]
+pub struct BatchRecord {
+    #[serde(with = "algorithm_serde")]
+    pub algorithm: &'static Algorithm,
+    pub root_hash: Vec<u8>,
+    pub count: usize,
+    pub keys: Vec<Option<String>>,
+    pub tree: MerkleTree<FileLeaf>,
+}
+
+/// One record per batch, and one per entry of the log, kept in the same storage
+/// as the files of the batches, under the `.catalog/` prefix.
+pub struct Catalog {
+    storage: Arc<dyn StorageBackend>,
+}
+
+impl Catalog {
+    pub fn new(storage: Arc<dyn StorageBackend>) -> Self {
+        Self { storage }
+    }
+
+    async fn read(&self, key: &str) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+        self.storage.get(key).await?.read_to_end(&mut bytes).await?;
+        Ok(bytes)
+    }
+
+    pub async fn save_batch(&self, batch_id: &BatchId, batch: &Batch) -> Result<()> {
+        let record = BatchRecord {
+            algorithm: batch.tree.algorithm,
+            root_hash: batch.tree.root_hash().clone(),
+            count: batch.tree.count(),
+            keys: batch.keys.clone(),
+            tree: batch.tree.clone(),
+        };
+        let key = format!("{}{}.json", BATCHES_PREFIX, batch_id);
+        self.storage
+            .put(&key, &mut serde_json::to_vec(&record)?.as_slice())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_batch(&self, batch_id: &BatchId) -> Result<()> {
+        let key = format!("{}{}.json", BATCHES_PREFIX, batch_id);
+        self.storage.delete(&key).await
+    }
+
+    fn log_entry_key(index: usize) -> String {
+        // Indices are padded, so that keys list in the order of the log.
+        format!("{}{:020}.json", LOG_PREFIX, index)
+    }
+
+    /// Saves the entry of the log at `index`. Entries are saved one by one,
+    /// so that a commit only writes its own entry.
+    pub async fn save_log_entry(&self, index: usize, entry: &LogEntry) -> Result<()> {
+        self.storage
+            .put(
+                &Self::log_entry_key(index),
+                &mut serde_json::to_vec(entry)?.as_slice(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes the entry of the log at `index`, e.g. that of a failed commit.
+    pub async fn delete_log_entry(&self, index: usize) -> Result<()> {
+        self.storage.delete(&Self::log_entry_key(index)).await
+    }
+
+    /// Loads the entries of the log, oldest first. A log saved as a whole
+    /// is stored again entry by entry first.
+    async fn load_log_entries(&self) -> Result<Vec<LogEntry>> {
+        if self.storage.stat(LEGACY_LOG_KEY).await?.is_some() {
+            let entries: Vec<LogEntry> = serde_json::from_slice(&self.read(LEGACY_LOG_KEY).await?)?;
+            for (index, entry) in entries.iter().enumerate() {
+                self.save_log_entry(index, entry).await?;
+            }
+            self.storage.delete(LEGACY_LOG_KEY).await?;
+        }
+
+        let mut entries = vec![];
+        for key in self.storage.list(LOG_PREFIX).await? {
+            if key != Self::log_entry_key(entries.len()) {
+                return Err(anyhow::Error::msg(format!(
+                    "log is corrupted: {} is not entry {}",
+                    key,
+                    entries.len()
+                )));
+            }
+            entries.push(serde_json::from_slice(&self.read(&key).await?)?);
+        }
+        Ok(entries)
+    }
+
+    /// Loads the log and every batch, checking that each batch matches its record,
+    /// down to the values of its leaves, and was logged. The files of the batches are checked by `verify_files`.
+    pub async fn load(&self) -> Result<(BTreeMap<BatchId, Batch>, Log)> {
+        let entries = self.load_log_entries().await?;
+        let log = Log::from_entries(common::DIGEST, common::PROFILE, entries)
+            .map_err(|err| anyhow::Error::msg(format!("log is corrupted: {}", err)))?;
+
+        let mut batches = BTreeMap::new();
+        for key in self.storage.list(BATCHES_PREFIX).await? {
+            let batch_id = match key
+                .strip_prefix(BATCHES_PREFIX)
+                .and_then(|name| name.strip_suffix(".json"))
+            {
+                Some(batch_id) => batch_id.parse::<BatchId>().map_err(anyhow::Error::msg)?,
+                None => continue,
+            };
+
+            let record: BatchRecord = serde_json::from_slice(&self.read(&key).await?)
+                .map_err(|err| anyhow::Error::msg(format!("batch {}: {}", batch_id, err)))?;
+            let tree = &record.tree;
+            if !std::ptr::eq(tree.algorithm, record.algorithm)
+                || !digests_match(tree.root_hash(), &record.root_hash)
+                || tree.count() != record.count
+                || record.keys.len() != record.count
+            {
+                return Err(anyhow::Error::msg(format!(
+                    "batch {}: the tree does not match the stored root",
+                    batch_id
+                )));
+            }
+            // Leaf values are deserialized as they are, so check them against the root.
+            tree.verify_leaves()
+                .map_err(|err| anyhow::Error::msg(format!("batch {}: {}", batch_id, err)))?;
+            let logged = log.entries().iter().any(|entry| {
+                entry.batch_id == batch_id.as_str()
+                    && digests_match(&entry.root_hash, &record.root_hash)
+            });
+            if !logged {
+                return Err(anyhow::Error::msg(format!(
+                    "batch {}: the root is not in the log",
+                    batch_id
+                )));
+            }
+
+            let batch = Batch {
+                tree: record.tree,
+                keys: record.keys,
+            };
+            batches.insert(batch_id, batch);
+        }
+        Ok((batches, log))
+    }
+}
+
+/// Checks that the stored files of `batch` still match the leaves of its tree.
+///
+/// The content hashes of a keyed batch cannot be checked without its key:
+/// only the sizes of its files are.
+pub async fn verify_files(
+    storage: &dyn StorageBackend,
+    batch_id: &str,
+    batch: &Batch,
+) -> Result<()> {
+    for (leaf, key) in batch.tree.leaves().zip(&batch.keys) {
+        let (leaf, key) = match key {
+            Some(key) => (leaf.value, key),
+            None => continue,
+        };
+        let mut reader =
+            HashingReader::new(storage.get(key).await?, Context::new(batch.tree.algorithm));
+        let size = tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+        let content_matches =
+            batch.tree.is_keyed() || digests_match(reader.finish().as_ref(), &leaf.content_hash);
+        if size != leaf.size || !content_matches {
+            return Err(anyhow::Error::msg(format!(
+                "batch {}: file {} no longer matches the stored root",
+                batch_id, leaf.path
+            )));
+        }
+    }
+    Ok(())
+}
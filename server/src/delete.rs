@@ -0,0 +1,112 @@
+use crate::{error::Error, State};
+use axum::{
+    extract::{rejection::PathRejection, Path},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    Extension,
+};
+use common::BatchId;
+use ring::constant_time::verify_slices_are_equal;
+use std::sync::Arc;
+use tracing::{error, info};
+
+/// Checks that the request bears the delete token of the server.
+/// Deletes are refused if the server has no token.
+fn authorize(state: &State, headers: &HeaderMap) -> Result<(), Error> {
+    let token = state.delete_token.as_ref().ok_or_else(|| {
+        Error::Unauthorized(format!(
+            "deletes are disabled, as {} is not set on the server",
+            common::DELETE_TOKEN
+        ))
+    })?;
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match bearer {
+        Some(bearer) if verify_slices_are_equal(bearer.as_bytes(), token.as_bytes()).is_ok() => {
+            Ok(())
+        }
+        _ => Err(Error::Unauthorized(
+            "the request does not bear the delete token".to_string(),
+        )),
+    }
+}
+
+/// Deletes a batch and its files. Its roots stay in the log.
+pub async fn delete_batch(state: &State, batch_id: &BatchId) -> Result<(), Error> {
+    let mut batch_tree_map = state.batch_tree_map.lock().await;
+    if !batch_tree_map.contains_key(batch_id) {
+        return Err(Error::NotFound(format!("no such batch {}", batch_id)));
+    }
+    // The record goes first, so that a batch is never loaded without its files.
+    state.catalog.delete_batch(batch_id).await?;
+    let batch = batch_tree_map.remove(batch_id);
+    drop(batch_tree_map);
+
+    for key in batch.into_iter().flat_map(|batch| batch.keys).flatten() {
+        if let Err(err) = state.storage.delete(&key).await {
+            error!("Deleted file {} was not removed, because: {:#}", key, err);
+        }
+    }
+    Ok(())
+}
+
+/// Deletes a file of a batch, and leaves a tombstone in its place: the leaf
+/// of the file is kept, so that the root and the proofs of other files still hold.
+pub async fn delete_file(state: &State, batch_id: &BatchId, file_index: u64) -> Result<(), Error> {
+    let mut batch_tree_map = state.batch_tree_map.lock().await;
+    let batch = batch_tree_map
+        .get_mut(batch_id)
+        .ok_or_else(|| Error::NotFound(format!("no such batch {}", batch_id)))?;
+    let count = batch.keys.len();
+    let key = usize::try_from(file_index)
+        .ok()
+        .and_then(|index| batch.keys.get_mut(index))
+        .ok_or_else(|| {
+            Error::NotFound(format!(
+                "batch {} has {} files, and no file {}",
+                batch_id, count, file_index
+            ))
+        })?;
+    // Deleting a file again changes nothing.
+    let key = match key.take() {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+    if let Err(err) = state.catalog.save_batch(batch_id, batch).await {
+        batch.keys[file_index as usize] = Some(key);
+        return Err(err.into());
+    }
+    drop(batch_tree_map);
+
+    if let Err(err) = state.storage.delete(&key).await {
+        error!("Deleted file {} was not removed, because: {:#}", key, err);
+    }
+    Ok(())
+}
+
+pub async fn delete_batch_handler(
+    state: Extension<Arc<State>>,
+    headers: HeaderMap,
+    batch_id: Result<Path<BatchId>, PathRejection>,
+) -> Result<StatusCode, Error> {
+    info!("About to delete a batch");
+    authorize(&state, &headers)?;
+    let Path(batch_id) = batch_id.map_err(|err| Error::BadRequest(err.body_text()))?;
+    delete_batch(&state, &batch_id).await?;
+    info!("Batch {} was deleted", batch_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_file_handler(
+    state: Extension<Arc<State>>,
+    headers: HeaderMap,
+    params: Result<Path<(BatchId, u64)>, PathRejection>,
+) -> Result<StatusCode, Error> {
+    info!("About to delete a file");
+    authorize(&state, &headers)?;
+    let Path((batch_id, file_index)) = params.map_err(|err| Error::BadRequest(err.body_text()))?;
+    delete_file(&state, &batch_id, file_index).await?;
+    info!("File {} of batch {} was deleted", file_index, batch_id);
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,16 +1,13 @@
-use crate::State;
-use anyhow::Result;
+use crate::{error::Error, storage::Reader, State};
 use axum::{
-    body::{boxed, StreamBody},
-    extract::Query,
-    http::StatusCode,
-    response::Response,
+    body::StreamBody,
+    extract::{rejection::QueryRejection, Query},
+    response::{IntoResponse, Response},
     Extension,
 };
 use common::BatchId;
 use serde_derive::Deserialize;
-use std::{pin::Pin, sync::Arc};
-use tokio::io::{AsyncRead, AsyncReadExt};
+use std::sync::Arc;
 use tokio_util::io::ReaderStream;
 use tracing::info;
 
@@ -24,25 +21,39 @@ pub async fn download(
     state: Extension<Arc<State>>,
     file_index: u64,
     batch_id: BatchId,
-) -> Result<(String, Pin<Box<dyn AsyncRead + Send + Sync>>)> {
+) -> Result<(String, Reader), Error> {
     let map = state.batch_tree_map.lock().await;
 
     let batch = map
         .get(&batch_id)
-        .ok_or(anyhow::Error::msg("no such batch"))?;
+        .ok_or(Error::NotFound(format!("no such batch {}", batch_id)))?;
 
-    let real_path = batch.paths[file_index as usize].clone();
-    let mut file = tokio::fs::File::open(real_path.clone()).await?;
+    let not_found = || {
+        Error::NotFound(format!(
+            "batch {} has {} files, and no file {}",
+            batch_id,
+            batch.keys.len(),
+            file_index
+        ))
+    };
+    let index = usize::try_from(file_index).map_err(|_| not_found())?;
+    let key = batch
+        .keys
+        .get(index)
+        .ok_or_else(not_found)?
+        .clone()
+        .ok_or_else(|| {
+            Error::NotFound(format!(
+                "file {} of batch {} was deleted",
+                file_index, batch_id
+            ))
+        })?;
+    let proof = batch.tree.gen_nth_proof(index).ok_or_else(not_found)?;
+    // The file is fetched without holding up other requests. If it is replaced
+    // or deleted meanwhile, it can no longer be fetched, and the download fails.
+    drop(map);
 
-    let mut bytes = Vec::new();
-    #[allow(clippy::let_underscore_future)]
-    let _ = file.read_to_end(&mut bytes);
-
-    let proof = batch
-        .tree
-        .gen_proof(bytes.clone())
-        .ok_or(anyhow::Error::msg("not found in tree"))?;
-    let stream = state.client.get_file(real_path).await?;
+    let stream = state.storage.get(&key).await?;
 
     let proof_string = serde_json::to_string(&proof)?;
 
@@ -51,26 +62,18 @@ pub async fn download(
 
 pub async fn download_handler(
     state: Extension<Arc<State>>,
-    Query(DownloadParams {
+    params: Result<Query<DownloadParams>, QueryRejection>,
+) -> Result<Response, Error> {
+    info!("About to downlaod a file");
+    let Query(DownloadParams {
         file_index,
         batch_id,
-    }): Query<DownloadParams>,
-) -> Response {
-    info!("About to downlaod a file");
-    match download(state, file_index, batch_id).await {
-        Ok((proof, stream)) => {
-            info!("Download was successful");
-            Response::builder()
-                .header(common::PROOF_HEADER, proof)
-                .body(boxed(StreamBody::new(ReaderStream::new(stream))))
-                .unwrap()
-        }
-        Err(err) => {
-            info!("Download failed, because: {}", err);
-            Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(boxed(err.to_string()))
-                .unwrap()
-        }
-    }
+    }) = params.map_err(|err| Error::BadRequest(err.body_text()))?;
+    let (proof, stream) = download(state, file_index, batch_id).await?;
+    info!("Download was successful");
+    Ok((
+        [(common::PROOF_HEADER, proof)],
+        StreamBody::new(ReaderStream::new(stream)),
+    )
+        .into_response())
 }
@@ -0,0 +1,105 @@
+//! The errors of handlers, answered with a status code and a JSON `ErrorBody`.
+
+use axum::{
+    extract::multipart::MultipartError,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use common::{ErrorBody, ErrorKind};
+use std::{fmt, io};
+use tracing::{error, info};
+
+#[derive(Debug)]
+pub enum Error {
+    /// No such batch, file or log entry, or the file was deleted: 404
+    NotFound(String),
+    /// The request is malformed, or does not match its manifest: 400
+    BadRequest(String),
+    /// The batch already exists: 409
+    Conflict(String),
+    /// The request lacks the delete token of the server: 401
+    Unauthorized(String),
+    /// The upload exceeds the size limit: 413
+    TooLarge(String),
+    /// Anything else, e.g. a failure of storage: 500
+    Internal(anyhow::Error),
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::NotFound(_) => ErrorKind::NotFound,
+            Error::BadRequest(_) => ErrorKind::BadRequest,
+            Error::Conflict(_) => ErrorKind::Conflict,
+            Error::Unauthorized(_) => ErrorKind::Unauthorized,
+            Error::TooLarge(_) => ErrorKind::TooLarge,
+            Error::Internal(_) => ErrorKind::Internal,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self.kind() {
+            ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ErrorKind::BadRequest => StatusCode::BAD_REQUEST,
+            ErrorKind::Conflict => StatusCode::CONFLICT,
+            ErrorKind::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorKind::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(message)
+            | Error::BadRequest(message)
+            | Error::Conflict(message)
+            | Error::Unauthorized(message)
+            | Error::TooLarge(message) => f.write_str(message),
+            Error::Internal(err) => write!(f, "{:#}", err),
+        }
+    }
+}
+
+/// Returns the error of the multipart body behind `err`, if any.
+/// The content of files is read through an `io::Error`.
+fn multipart_error(err: &anyhow::Error) -> Option<&MultipartError> {
+    err.downcast_ref::<MultipartError>().or_else(|| {
+        err.downcast_ref::<io::Error>()?
+            .get_ref()?
+            .downcast_ref::<MultipartError>()
+    })
+}
+
+/// Errors of the multipart body are the client's, anything else is the server's.
+impl<E: Into<anyhow::Error>> From<E> for Error {
+    fn from(err: E) -> Self {
+        let err = err.into();
+        match multipart_error(&err).map(|multipart| (multipart.status(), multipart.body_text())) {
+            Some((StatusCode::PAYLOAD_TOO_LARGE, message)) => Error::TooLarge(message),
+            Some((status, message)) if status.is_client_error() => Error::BadRequest(message),
+            _ => Error::Internal(err),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        match &self {
+            Error::Internal(err) => error!("Request failed, because: {:#}", err),
+            _ => info!("Request was refused, because: {}", self),
+        }
+        // The causes of internal errors, e.g. storage paths, are only logged.
+        let message = match &self {
+            Error::Internal(_) => "internal server error".to_string(),
+            _ => self.to_string(),
+        };
+        let body = ErrorBody {
+            kind: self.kind(),
+            message,
+        };
+        (self.status(), Json(body)).into_response()
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{error::Error, State};
+use axum::{
+    extract::{rejection::QueryRejection, Query},
+    Extension, Json,
+};
+use common::BatchId;
+use merkle_tree::{
+    consistency::ConsistencyProof,
+    log::{LogEntry, LogHead},
+    proof::Proof,
+};
+use serde_derive::Deserialize;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct InclusionParams {
+    batch_id: BatchId,
+    /// The size of the log to prove against, the current one by default
+    size: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct ConsistencyParams {
+    old_size: usize,
+    /// The size of the newer log, the current one by default
+    new_size: Option<usize>,
+}
+
+pub async fn log_head_handler(state: Extension<Arc<State>>) -> Json<LogHead> {
+    Json(state.log.lock().await.head())
+}
+
+pub async fn inclusion_handler(
+    state: Extension<Arc<State>>,
+    params: Result<Query<InclusionParams>, QueryRejection>,
+) -> Result<Json<Proof<LogEntry>>, Error> {
+    let Query(InclusionParams { batch_id, size }) =
+        params.map_err(|err| Error::BadRequest(err.body_text()))?;
+    let log = state.log.lock().await;
+    let size = size.unwrap_or(log.size());
+    if size > log.size() {
+        return Err(Error::NotFound(format!(
+            "the log has {} entries, not {}",
+            log.size(),
+            size
+        )));
+    }
+    let proof = log
+        .find(&batch_id, size)
+        .and_then(|index| log.inclusion_proof(index, size))
+        .ok_or(Error::NotFound(format!(
+            "no batch {} in the first {} entries of the log",
+            batch_id, size
+        )))?;
+    Ok(Json(proof))
+}
+
+pub async fn consistency_handler(
+    state: Extension<Arc<State>>,
+    params: Result<Query<ConsistencyParams>, QueryRejection>,
+) -> Result<Json<ConsistencyProof>, Error> {
+    let Query(ConsistencyParams { old_size, new_size }) =
+        params.map_err(|err| Error::BadRequest(err.body_text()))?;
+    let log = state.log.lock().await;
+    let new_size = new_size.unwrap_or(log.size());
+    if new_size > log.size() {
+        return Err(Error::NotFound(format!(
+            "the log has {} entries, not {}",
+            log.size(),
+            new_size
+        )));
+    }
+    let proof = log
+        .consistency_proof(old_size, new_size)
+        .ok_or(Error::BadRequest(format!(
+            "the old size {} is larger than the new size {}",
+            old_size, new_size
+        )))?;
+    Ok(Json(proof))
+}
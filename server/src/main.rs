@@ -1,34 +1,112 @@
 use anyhow::Result;
+use append::append_handler;
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{on, MethodFilter},
     Extension, Router,
 };
 use batch::Batch;
-use client::Client;
-use common::{ADDRESS, DEFAULT_ADDRESS, DEFAULT_PORT, DOWNLOAD_ROUTE, PORT, UPLOAD_ROUTE};
+use catalog::Catalog;
+use common::{
+    BatchId, ADDRESS, APPEND_ROUTE, BATCH_ROUTE, DEFAULT_ADDRESS, DEFAULT_PORT, DELETE_TOKEN,
+    DOWNLOAD_ROUTE, FILE_ROUTE, LOG_CONSISTENCY_ROUTE, LOG_HEAD_ROUTE, LOG_INCLUSION_ROUTE, PORT,
+    UPLOAD_ROUTE,
+};
+use delete::{delete_batch_handler, delete_file_handler};
 use download::download_handler;
+use log::{consistency_handler, inclusion_handler, log_head_handler};
+use merkle_tree::log::Log;
+use s3::{S3Config, S3Storage};
 use std::{
     collections::BTreeMap,
     net::{Ipv4Addr, SocketAddr},
     path::PathBuf,
     sync::Arc,
 };
+use storage::{LocalStorage, StorageBackend};
 use tokio::sync::Mutex;
 use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
 use upload::upload_handler;
 
+pub mod append;
 pub mod batch;
-pub mod client;
+pub mod catalog;
+pub mod delete;
 pub mod download;
+pub mod error;
+pub mod log;
+pub mod s3;
+pub mod storage;
 pub mod upload;
 
 const FILE_DIR_PATH: &str = "FILE_DIR_PATH";
 const DEFAULT_FILE_DIR_PATH: &str = ".";
+/// Where files are stored: `fs` for `FILE_DIR_PATH`, the default,
+/// or `s3` for the bucket configured with the `S3_*` variables.
+const STORAGE_BACKEND: &str = "STORAGE_BACKEND";
+/// The size limit of the body of an upload, in bytes.
+const MAX_UPLOAD_SIZE: &str = "MAX_UPLOAD_SIZE";
+const DEFAULT_MAX_UPLOAD_SIZE: usize = 1 << 30;
 
 pub struct State {
-    pub client: Arc<Client>,
-    pub batch_tree_map: Mutex<BTreeMap<String, Batch>>,
+    pub storage: Arc<dyn StorageBackend>,
+    pub batch_tree_map: Mutex<BTreeMap<BatchId, Batch>>,
+    /// The log of the roots of every accepted batch
+    pub log: Mutex<Log>,
+    /// Where batches and the log are persisted, in `storage`
+    pub catalog: Catalog,
+    /// The bearer token of delete requests, which are refused without one
+    pub delete_token: Option<String>,
+}
+
+impl State {
+    /// Loads the batches and the log from the catalog in `storage`,
+    /// and checks that the files of every batch still match its root.
+    /// Files of uploads that were not committed are removed.
+    pub async fn load(storage: Arc<dyn StorageBackend>) -> Result<State> {
+        // Uploads that were interrupted are not resumed.
+        storage::delete_all(storage.as_ref(), upload::STAGING_PREFIX).await?;
+        let catalog = Catalog::new(storage.clone());
+        let (batch_tree_map, log) = catalog.load().await?;
+        for (batch_id, batch) in &batch_tree_map {
+            catalog::verify_files(storage.as_ref(), batch_id, batch).await?;
+        }
+
+        info!(
+            "Catalog is loaded, with {} batches and {} log entries",
+            batch_tree_map.len(),
+            log.size()
+        );
+
+        Ok(State {
+            storage,
+            batch_tree_map: Mutex::new(batch_tree_map),
+            log: Mutex::new(log),
+            catalog,
+            delete_token: None,
+        })
+    }
+}
+
+pub fn router(state: Arc<State>, max_upload_size: usize) -> Router {
+    Router::new()
+        .route(UPLOAD_ROUTE, on(MethodFilter::POST, upload_handler))
+        .route(APPEND_ROUTE, on(MethodFilter::POST, append_handler))
+        .route(BATCH_ROUTE, on(MethodFilter::DELETE, delete_batch_handler))
+        .route(FILE_ROUTE, on(MethodFilter::DELETE, delete_file_handler))
+        .route(DOWNLOAD_ROUTE, on(MethodFilter::GET, download_handler))
+        .route(LOG_HEAD_ROUTE, on(MethodFilter::GET, log_head_handler))
+        .route(
+            LOG_INCLUSION_ROUTE,
+            on(MethodFilter::GET, inclusion_handler),
+        )
+        .route(
+            LOG_CONSISTENCY_ROUTE,
+            on(MethodFilter::GET, consistency_handler),
+        )
+        .layer(DefaultBodyLimit::max(max_upload_size))
+        .layer(Extension(state))
 }
 
 #[tokio::main]
@@ -38,23 +116,32 @@ async fn main() -> Result<()> {
         .finish();
     tracing::subscriber::set_global_default(subscriber)?;
 
-    let client = Arc::new(client::Client::new(PathBuf::from(
+    let file_dir = PathBuf::from(
         std::env::var(FILE_DIR_PATH)
             .ok()
             .unwrap_or(DEFAULT_FILE_DIR_PATH.to_string()),
-    )));
-
-    info!("Client is ready");
+    );
+    let storage: Arc<dyn StorageBackend> = match std::env::var(STORAGE_BACKEND).as_deref() {
+        Ok("s3") => Arc::new(S3Storage::new(S3Config::from_env()?)),
+        Ok("fs") | Err(_) => Arc::new(LocalStorage::new(file_dir)),
+        Ok(other) => {
+            return Err(anyhow::Error::msg(format!(
+                "unknown storage backend {}",
+                other
+            )))
+        }
+    };
 
-    let state = Arc::new(State {
-        client,
-        batch_tree_map: Default::default(),
-    });
+    info!("Storage is ready");
 
-    let router = Router::new()
-        .route(UPLOAD_ROUTE, on(MethodFilter::POST, upload_handler))
-        .route(DOWNLOAD_ROUTE, on(MethodFilter::GET, download_handler))
-        .layer(Extension(state));
+    let mut state = State::load(storage).await?;
+    state.delete_token = std::env::var(DELETE_TOKEN).ok();
+    let state = Arc::new(state);
+    let max_upload_size: usize = std::env::var(MAX_UPLOAD_SIZE)
+        .ok()
+        .unwrap_or(DEFAULT_MAX_UPLOAD_SIZE.to_string())
+        .parse()?;
+    let router = router(state, max_upload_size);
 
     info!("Router is ready");
 
@@ -79,3 +166,6 @@ async fn main() -> Result<()> {
     info!("Going down");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests;
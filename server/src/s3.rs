@@ -0,0 +1,445 @@
+//! A storage backend for S3-compatible object stores, such as AWS S3 or MinIO.
+//!
+//! Objects are addressed path-style, as `endpoint/bucket/key`, and requests are
+//! signed with AWS Signature Version 4.
+
+use crate::storage::{check_key, Reader, StorageBackend};
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use reqwest::{Method, StatusCode, Url};
+use ring::{digest, hmac};
+use std::{
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::io::StreamReader;
+
+const S3_ENDPOINT: &str = "S3_ENDPOINT";
+const S3_BUCKET: &str = "S3_BUCKET";
+const S3_REGION: &str = "S3_REGION";
+const DEFAULT_S3_REGION: &str = "us-east-1";
+const S3_ACCESS_KEY_ID: &str = "S3_ACCESS_KEY_ID";
+const S3_SECRET_ACCESS_KEY: &str = "S3_SECRET_ACCESS_KEY";
+/// Objects are uploaded in parts of this size, so that one part at a time is held in memory.
+const PART_SIZE: usize = 8 << 20;
+/// The largest object that S3 copies in a single request.
+const MAX_COPY_SIZE: u64 = 5 << 30;
+
+#[derive(Clone, Debug)]
+pub struct S3Config {
+    /// The URL of the object store, e.g. `http://127.0.0.1:9000`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+impl S3Config {
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| {
+            std::env::var(name).map_err(|_| anyhow::Error::msg(format!("{} is not set", name)))
+        };
+        Ok(S3Config {
+            endpoint: var(S3_ENDPOINT)?,
+            bucket: var(S3_BUCKET)?,
+            region: var(S3_REGION).unwrap_or(DEFAULT_S3_REGION.to_string()),
+            access_key_id: var(S3_ACCESS_KEY_ID)?,
+            secret_access_key: var(S3_SECRET_ACCESS_KEY)?,
+        })
+    }
+
+    /// Returns the value of the `Authorization` header of a request.
+    ///
+    /// `headers` are the signed headers, with lowercase names, sorted by name,
+    /// and `amz_date` is the value of the `x-amz-date` header.
+    pub(crate) fn authorization(
+        &self,
+        method: &str,
+        canonical_uri: &str,
+        canonical_query: &str,
+        headers: &[(&str, &str)],
+        payload_hash: &str,
+        amz_date: &str,
+    ) -> String {
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method, canonical_uri, canonical_query, canonical_headers, signed_headers, payload_hash
+        );
+
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(digest::digest(
+                &digest::SHA256,
+                canonical_request.as_bytes()
+            ))
+        );
+
+        let sign = |key: &[u8], data: &str| {
+            hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data.as_bytes())
+        };
+        let key = format!("AWS4{}", self.secret_access_key);
+        let key = sign(key.as_bytes(), date);
+        let key = sign(key.as_ref(), &self.region);
+        let key = sign(key.as_ref(), "s3");
+        let key = sign(key.as_ref(), "aws4_request");
+        let signature = sign(key.as_ref(), &string_to_sign);
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{},SignedHeaders={},Signature={}",
+            self.access_key_id,
+            scope,
+            signed_headers,
+            hex::encode(signature)
+        )
+    }
+}
+
+/// Percent-encodes `value` as SigV4 expects, keeping `/` if `keep_slash` is set.
+pub(crate) fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Formats a time as `YYYYMMDDTHHMMSSZ`, in UTC.
+pub(crate) fn amz_date(time: SystemTime) -> Result<String> {
+    let secs = time.duration_since(UNIX_EPOCH)?.as_secs();
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // Converts days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    Ok(format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    ))
+}
+
+/// Returns the raw text of every `<tag>` element of `xml`.
+fn xml_elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    xml.split(open.as_str())
+        .skip(1)
+        .filter_map(|rest| rest.split_once(close.as_str()).map(|(text, _)| text))
+        .collect()
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Reads up to `size` bytes of `reader`, fewer only at the end of its content.
+async fn read_part(reader: &mut (dyn AsyncRead + Unpin + Send), size: usize) -> Result<Vec<u8>> {
+    let mut part = vec![];
+    reader.take(size as u64).read_to_end(&mut part).await?;
+    Ok(part)
+}
+
+pub struct S3Storage {
+    config: S3Config,
+    client: reqwest::Client,
+    part_size: usize,
+}
+
+impl S3Storage {
+    pub fn new(config: S3Config) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            part_size: PART_SIZE,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_part_size(mut self, part_size: usize) -> Self {
+        self.part_size = part_size;
+        self
+    }
+
+    /// Sends a signed request for `key`, or for the bucket itself if `key` is `None`,
+    /// with the `x-amz-*` headers of `amz_headers`, which are signed too.
+    async fn send(
+        &self,
+        method: Method,
+        key: Option<&str>,
+        query: &[(&str, &str)],
+        amz_headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response> {
+        let mut canonical_uri = format!("/{}", uri_encode(&self.config.bucket, false));
+        if let Some(key) = key {
+            check_key(key)?;
+            canonical_uri = format!("{}/{}", canonical_uri, uri_encode(key, true));
+        }
+        let mut query: Vec<_> = query
+            .iter()
+            .map(|(name, value)| {
+                format!("{}={}", uri_encode(name, false), uri_encode(value, false))
+            })
+            .collect();
+        query.sort();
+        let canonical_query = query.join("&");
+
+        let mut url = Url::parse(&self.config.endpoint)?.join(&canonical_uri)?;
+        if !canonical_query.is_empty() {
+            url.set_query(Some(&canonical_query));
+        }
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(anyhow::Error::msg("the S3 endpoint has no host")),
+        };
+        let payload_hash = hex::encode(digest::digest(&digest::SHA256, &body));
+        let amz_date = amz_date(SystemTime::now())?;
+        let mut headers = vec![
+            ("host", host.as_str()),
+            ("x-amz-content-sha256", &payload_hash),
+            ("x-amz-date", &amz_date),
+        ];
+        headers.extend_from_slice(amz_headers);
+        headers.sort();
+        let authorization = self.config.authorization(
+            method.as_str(),
+            &canonical_uri,
+            &canonical_query,
+            &headers,
+            &payload_hash,
+            &amz_date,
+        );
+
+        let mut request = self.client.request(method, url);
+        for (name, value) in &headers[..] {
+            if *name != "host" {
+                request = request.header(*name, *value);
+            }
+        }
+        Ok(request
+            .header("authorization", authorization)
+            .body(body)
+            .send()
+            .await?)
+    }
+
+    /// Uploads `first` and the rest of `reader` as the parts of the multipart upload
+    /// `upload_id`, and completes it.
+    async fn put_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        first: Vec<u8>,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+    ) -> Result<u64> {
+        let mut size = 0;
+        let mut parts = String::new();
+        let mut part = first;
+        let mut part_number = 1;
+        while !part.is_empty() {
+            size += part.len() as u64;
+            let number = part_number.to_string();
+            let query = [("partNumber", number.as_str()), ("uploadId", upload_id)];
+            let response =
+                check_status(self.send(Method::PUT, Some(key), &query, &[], part).await?).await?;
+            let etag = response
+                .headers()
+                .get(reqwest::header::ETAG)
+                .ok_or(anyhow::Error::msg("S3 response has no ETag"))?
+                .to_str()?;
+            parts.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                part_number, etag
+            ));
+            part = read_part(reader, self.part_size).await?;
+            part_number += 1;
+        }
+
+        let body = format!(
+            "<CompleteMultipartUpload>{}</CompleteMultipartUpload>",
+            parts
+        );
+        let query = [("uploadId", upload_id)];
+        let response = self
+            .send(Method::POST, Some(key), &query, &[], body.into_bytes())
+            .await?;
+        // The upload may still fail after the response has started, with a status of 200.
+        let body = check_status(response).await?.text().await?;
+        if body.contains("<Error>") {
+            return Err(anyhow::Error::msg(format!("S3 request failed: {}", body)));
+        }
+        Ok(size)
+    }
+}
+
+/// Turns an unsuccessful response into an error.
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    Err(anyhow::Error::msg(format!(
+        "S3 request failed with {}: {}",
+        response.status(),
+        response.text().await.unwrap_or_default()
+    )))
+}
+
+#[async_trait]
+impl StorageBackend for S3Storage {
+    /// Content larger than a part is sent as a multipart upload, as every request
+    /// is signed with the digest of its body, which is held in memory.
+    async fn put(&self, key: &str, reader: &mut (dyn AsyncRead + Unpin + Send)) -> Result<u64> {
+        let first = read_part(reader, self.part_size).await?;
+        if first.len() < self.part_size {
+            let size = first.len() as u64;
+            check_status(self.send(Method::PUT, Some(key), &[], &[], first).await?).await?;
+            return Ok(size);
+        }
+
+        let response = self
+            .send(Method::POST, Some(key), &[("uploads", "")], &[], vec![])
+            .await?;
+        let body = check_status(response).await?.text().await?;
+        let upload_id = xml_elements(&body, "UploadId")
+            .first()
+            .map(|id| xml_unescape(id))
+            .ok_or(anyhow::Error::msg("S3 response has no UploadId"))?;
+        let result = self.put_parts(key, &upload_id, first, reader).await;
+        if result.is_err() {
+            // Parts that were uploaded are kept by the store until the upload is aborted.
+            let query = [("uploadId", upload_id.as_str())];
+            if let Ok(response) = self
+                .send(Method::DELETE, Some(key), &query, &[], vec![])
+                .await
+            {
+                check_status(response).await.ok();
+            }
+        }
+        result
+    }
+
+    async fn get(&self, key: &str) -> Result<Reader> {
+        let response =
+            check_status(self.send(Method::GET, Some(key), &[], &[], vec![]).await?).await?;
+        let stream = response
+            .bytes_stream()
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+        Ok(Box::pin(StreamReader::new(stream)))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        check_status(
+            self.send(Method::DELETE, Some(key), &[], &[], vec![])
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// The content is copied on the server, and then deleted.
+    /// Objects larger than 5 GiB cannot be copied in one request, and are refused.
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        check_key(from)?;
+        if let Some(size) = self.stat(from).await? {
+            if size > MAX_COPY_SIZE {
+                return Err(anyhow::Error::msg(format!(
+                    "{} has {} bytes, and S3 copies at most {}",
+                    from, size, MAX_COPY_SIZE
+                )));
+            }
+        }
+        let source = format!(
+            "/{}/{}",
+            uri_encode(&self.config.bucket, false),
+            uri_encode(from, true)
+        );
+        let copy_source = [("x-amz-copy-source", source.as_str())];
+        check_status(
+            self.send(Method::PUT, Some(to), &[], &copy_source, vec![])
+                .await?,
+        )
+        .await?;
+        self.delete(from).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = vec![];
+        let mut continuation_token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(token) = &continuation_token {
+                query.push(("continuation-token", token));
+            }
+            let response = self.send(Method::GET, None, &query, &[], vec![]).await?;
+            let body = check_status(response).await?.text().await?;
+
+            for contents in xml_elements(&body, "Contents") {
+                keys.extend(xml_elements(contents, "Key").into_iter().map(xml_unescape));
+            }
+            let truncated = xml_elements(&body, "IsTruncated").contains(&"true");
+            continuation_token = xml_elements(&body, "NextContinuationToken")
+                .first()
+                .map(|token| xml_unescape(token));
+            if !truncated || continuation_token.is_none() {
+                break;
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn stat(&self, key: &str) -> Result<Option<u64>> {
+        let response = self.send(Method::HEAD, Some(key), &[], &[], vec![]).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = check_status(response).await?;
+        let size = response
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .ok_or(anyhow::Error::msg("S3 response has no content length"))?
+            .to_str()?
+            .parse()?;
+        Ok(Some(size))
+    }
+}
@@ -0,0 +1,277 @@
+//! Where the files of batches are stored.
+//!
+//! Files are addressed by keys such as `batch_id/file_name`: relative paths with `/`
+//! as separator, which every backend maps to its own namespace.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::BatchId;
+use merkle_tree::leaf::FileLeaf;
+use std::{
+    collections::BTreeMap,
+    io::Cursor,
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub type Reader = Pin<Box<dyn AsyncRead + Send>>;
+
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Stores the content of `reader` under `key`, replacing any previous content,
+    /// and returns the number of bytes stored.
+    async fn put(&self, key: &str, reader: &mut (dyn AsyncRead + Unpin + Send)) -> Result<u64>;
+
+    /// Returns the content stored under `key`.
+    async fn get(&self, key: &str) -> Result<Reader>;
+
+    /// Removes the content stored under `key`, if any.
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Moves the content stored under `from` to `to`, replacing any content there.
+    async fn rename(&self, from: &str, to: &str) -> Result<()>;
+
+    /// Returns the keys that start with `prefix`, in order.
+    async fn list(&self, prefix: &str) -> Result<Vec<String>>;
+
+    /// Returns the size in bytes of the content stored under `key`, if any.
+    async fn stat(&self, key: &str) -> Result<Option<u64>>;
+}
+
+/// Returns the key of the file named `name` in the batch `batch_id`,
+/// as stored by the upload `upload_id`.
+///
+/// Every upload stores its files in its own directory, so that replacing a batch
+/// never overwrites the files that are still served.
+pub fn file_key(batch_id: &BatchId, upload_id: &str, name: &str) -> String {
+    format!("{}/{}/{}", batch_id, upload_id, encode_name(name))
+}
+
+/// Percent-encodes a file name, except for ASCII letters, digits, `-`, `_` and `.`
+/// after the first character, so that any name maps to a single plain path component,
+/// which is neither hidden nor reserved.
+pub fn encode_name(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for (i, byte) in name.bytes().enumerate() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => encoded.push(byte as char),
+            b'.' if i > 0 => encoded.push('.'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Removes every key that starts with `prefix`.
+pub async fn delete_all(storage: &dyn StorageBackend, prefix: &str) -> Result<()> {
+    for key in storage.list(prefix).await? {
+        storage.delete(&key).await?;
+    }
+    Ok(())
+}
+
+/// Checks that `key` is a relative path that cannot escape the namespace of a backend.
+pub(crate) fn check_key(key: &str) -> Result<()> {
+    if !FileLeaf::is_safe_path(key) {
+        return Err(anyhow::Error::msg(format!("invalid storage key {}", key)));
+    }
+    Ok(())
+}
+
+/// Distinguishes the temporary files of concurrent writes.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn is_tmp_file(name: &str) -> bool {
+    name.starts_with('.') && name.ends_with(".tmp")
+}
+
+/// Stores files in a directory of the local filesystem.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        check_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    /// The content is written to a temporary file, which is then renamed,
+    /// so that readers never see partial content.
+    async fn put(&self, key: &str, reader: &mut (dyn AsyncRead + Unpin + Send)) -> Result<u64> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let tmp_path = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        let written = async {
+            let written = tokio::io::copy(reader, &mut file).await?;
+            file.sync_all().await?;
+            Ok::<_, std::io::Error>(written)
+        }
+        .await;
+        match written {
+            Ok(written) => {
+                tokio::fs::rename(&tmp_path, path).await?;
+                Ok(written)
+            }
+            Err(err) => {
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+                Err(err.into())
+            }
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Reader> {
+        Ok(Box::pin(tokio::fs::File::open(self.path(key)?).await?))
+    }
+
+    /// Directories left empty are removed as well.
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+        for dir in path.ancestors().skip(1) {
+            // Fails once a directory is not empty.
+            if dir == self.root || tokio::fs::remove_dir(dir).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let (from, to) = (self.path(from)?, self.path(to)?);
+        if let Some(parent) = to.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::rename(from, to).await?;
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = vec![];
+        let mut dirs = vec![(self.root.clone(), String::new())];
+        while let Some((dir, dir_key)) = dirs.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let name = match entry.file_name().into_string() {
+                    Ok(name) => name,
+                    Err(_) => continue,
+                };
+                let key = format!("{}{}", dir_key, name);
+                if entry.file_type().await?.is_dir() {
+                    let dir_key = format!("{}/", key);
+                    // Only walk directories that may hold keys with the prefix.
+                    if dir_key.starts_with(prefix) || prefix.starts_with(&dir_key) {
+                        dirs.push((entry.path(), dir_key));
+                    }
+                } else if key.starts_with(prefix) && !is_tmp_file(&name) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn stat(&self, key: &str) -> Result<Option<u64>> {
+        match tokio::fs::metadata(self.path(key)?).await {
+            Ok(metadata) if metadata.is_file() => Ok(Some(metadata.len())),
+            Ok(_) => Ok(None),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Stores files in memory, e.g. for tests.
+#[derive(Default)]
+pub struct MemoryStorage {
+    objects: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+#[async_trait]
+impl StorageBackend for MemoryStorage {
+    async fn put(&self, key: &str, reader: &mut (dyn AsyncRead + Unpin + Send)) -> Result<u64> {
+        check_key(key)?;
+        let mut content = vec![];
+        let size = reader.read_to_end(&mut content).await? as u64;
+        self.objects
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), content);
+        Ok(size)
+    }
+
+    async fn get(&self, key: &str) -> Result<Reader> {
+        let content = self
+            .objects
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .ok_or(anyhow::Error::msg(format!("no such key {}", key)))?;
+        Ok(Box::pin(Cursor::new(content)))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.objects.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        check_key(to)?;
+        let mut objects = self.objects.lock().unwrap();
+        let content = objects
+            .remove(from)
+            .ok_or(anyhow::Error::msg(format!("no such key {}", from)))?;
+        objects.insert(to.to_string(), content);
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .objects
+            .lock()
+            .unwrap()
+            .range(prefix.to_string()..)
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    async fn stat(&self, key: &str) -> Result<Option<u64>> {
+        Ok(self
+            .objects
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|content| content.len() as u64))
+    }
+}
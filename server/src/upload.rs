@@ -1,92 +1,381 @@
-use crate::{Batch, State};
-use anyhow::Result;
+use crate::{
+    error::Error,
+    storage::{self, encode_name, file_key},
+    Batch, State,
+};
 use axum::{
-    body::boxed,
-    extract::{Multipart, Query},
+    extract::{multipart::MultipartRejection, rejection::QueryRejection, Multipart, Query},
     http::StatusCode,
-    response::Response,
-    Extension,
+    Extension, Json,
 };
-use common::BatchId;
+use common::{BatchId, ReceiptLeaf, UploadReceipt};
 use futures::TryStreamExt;
-use merkle_tree::merkletree::MerkleTree;
+use merkle_tree::{
+    hashing::HashingReader, leaf::FileLeaf, merkletree::MerkleTree, proof::digests_match,
+};
+use ring::{
+    digest::Context,
+    rand::{SecureRandom, SystemRandom},
+};
 use serde_derive::Deserialize;
-use std::sync::Arc;
-use std::{io, path::PathBuf};
-use tokio::io::AsyncReadExt;
+use std::io;
+use std::{collections::BTreeMap, sync::Arc};
 use tokio_util::io::StreamReader;
-use tracing::info;
+use tracing::{error, info};
+
+/// Where the files of an upload are written until all of them are received,
+/// in one directory per upload.
+pub const STAGING_PREFIX: &str = ".staging/";
 
 #[derive(Deserialize)]
 pub struct UploadParams {
-    batch_id: common::BatchId,
+    batch_id: BatchId,
+    /// Whether an existing batch with other files is replaced, instead of a conflict
+    #[serde(default)]
+    replace: bool,
 }
 
-pub async fn upload(
-    state: Extension<Arc<State>>,
-    batch_id: BatchId,
-    mut multipart: Multipart,
-) -> Result<()> {
-    let mut filename_vec = vec![];
+/// Returns a random id for an upload, which names the directories of its files.
+pub(crate) fn upload_id() -> Result<String, Error> {
+    let mut id = [0; 8];
+    SystemRandom::new()
+        .fill(&mut id)
+        .map_err(|_| anyhow::Error::msg("could not generate an upload id"))?;
+    Ok(hex::encode(id))
+}
+
+pub fn receipt(batch_id: &BatchId, tree: &MerkleTree<FileLeaf>) -> UploadReceipt {
+    UploadReceipt {
+        batch_id: batch_id.clone(),
+        algorithm: tree.algorithm,
+        count: tree.count() as u64,
+        root_hash: tree.root_hash().clone(),
+        leaves: tree
+            .leaves()
+            .map(|leaf| ReceiptLeaf {
+                path: leaf.value.path.clone(),
+                leaf_hash: leaf.hash.to_vec(),
+            })
+            .collect(),
+    }
+}
+
+/// Returns the receipt of the stored batch if it has the same root as `tree`,
+/// as uploading it again changes nothing, and a conflict if it has another root
+/// and may not be replaced. A batch with the same root whose files were deleted
+/// is uploaded again, which restores them. Files appended to a batch are not in
+/// canonical order, so such a batch has another root than the same files uploaded again.
+fn existing_receipt(
+    batch_tree_map: &BTreeMap<BatchId, Batch>,
+    batch_id: &BatchId,
+    tree: &MerkleTree<FileLeaf>,
+    replace: bool,
+) -> Result<Option<UploadReceipt>, Error> {
+    match batch_tree_map.get(batch_id) {
+        Some(batch)
+            if std::ptr::eq(batch.tree.algorithm, tree.algorithm)
+                && digests_match(batch.tree.root_hash(), tree.root_hash()) =>
+        {
+            if batch.keys.iter().all(Option::is_some) {
+                Ok(Some(receipt(batch_id, &batch.tree)))
+            } else {
+                Ok(None)
+            }
+        }
+        Some(batch) if !replace => {
+            let mut leaves: Vec<&FileLeaf> = batch.tree.leaves().map(|leaf| leaf.value).collect();
+            leaves.sort_by(|a, b| a.cmp_canonical(b));
+            let files: Vec<&FileLeaf> = tree.leaves().map(|leaf| leaf.value).collect();
+            Err(Error::Conflict(if leaves == files {
+                format!(
+                    "batch {} has the same files, in the order in which they were appended; \
+                     upload it again with --replace",
+                    batch_id
+                )
+            } else {
+                format!("batch {} already exists with other files", batch_id)
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Builds the tree of a manifest, whose files may be listed in any order.
+fn manifest_tree(bytes: &[u8]) -> Result<MerkleTree<FileLeaf>, Error> {
+    let mut leaves: Vec<FileLeaf> = serde_json::from_slice(bytes)
+        .map_err(|err| Error::BadRequest(format!("invalid manifest: {}", err)))?;
+    leaves.sort_by(FileLeaf::cmp_canonical);
+    if !FileLeaf::is_canonical_order(&leaves) {
+        return Err(Error::BadRequest(
+            "the manifest lists a file twice".to_string(),
+        ));
+    }
+    Ok(MerkleTree::from_vec_with_profile(
+        common::DIGEST,
+        common::PROFILE,
+        leaves,
+    ))
+}
+
+/// Checks the tree of a keyed batch, whose hashes cannot be computed here.
+fn keyed_tree(bytes: &[u8]) -> Result<MerkleTree<FileLeaf>, Error> {
+    let tree: MerkleTree<FileLeaf> = serde_json::from_slice(bytes)
+        .map_err(|err| Error::BadRequest(format!("invalid tree: {}", err)))?;
+    if !tree.is_keyed() || !std::ptr::eq(tree.algorithm, common::DIGEST) {
+        return Err(Error::BadRequest(
+            "only keyed trees can be uploaded".to_string(),
+        ));
+    }
+    let leaves: Vec<FileLeaf> = tree.leaves().map(|leaf| leaf.value.clone()).collect();
+    if !FileLeaf::is_canonical_order(&leaves) {
+        return Err(Error::BadRequest(
+            "the leaves of the tree must be sorted by name, without duplicates".to_string(),
+        ));
+    }
+    Ok(tree)
+}
+
+/// Removes what is left of the upload `upload_id` in the staging area,
+/// and the files it moved to the batch unless they were `committed`.
+pub(crate) async fn clean_up(state: &State, batch_id: &BatchId, upload_id: &str, committed: bool) {
+    let mut prefixes = vec![format!("{}{}/", STAGING_PREFIX, upload_id)];
+    if !committed {
+        prefixes.push(format!("{}/{}/", batch_id, upload_id));
+    }
+    for prefix in prefixes {
+        if let Err(err) = storage::delete_all(state.storage.as_ref(), &prefix).await {
+            error!(
+                "Files under {} were not removed, because: {:#}",
+                prefix, err
+            );
+        }
+    }
+}
 
-    let mut bytes_vec = vec![];
-    let mut paths = vec![];
+/// Receives the manifest, or the tree of a keyed batch, which is sent before the files.
+pub(crate) async fn receive_manifest(
+    multipart: &mut Multipart,
+) -> Result<MerkleTree<FileLeaf>, Error> {
+    while let Some(field) = multipart.next_field().await? {
+        let is_manifest = field.name() == Some(common::MANIFEST_FIELD);
+        if is_manifest || field.name() == Some(common::TREE_FIELD) {
+            // The hashes of a keyed tree cannot be computed here, so the client
+            // sends the whole tree, whose leaves are the manifest.
+            let bytes = field.bytes().await?;
+            return if is_manifest {
+                manifest_tree(&bytes)
+            } else {
+                keyed_tree(&bytes)
+            };
+        }
+        if field.file_name().is_some() {
+            break;
+        }
+    }
+    Err(Error::BadRequest(
+        "the manifest must be sent before the files".to_string(),
+    ))
+}
 
-    let batch_dir = PathBuf::from(".").join(PathBuf::from(batch_id.clone()));
-    std::fs::create_dir(batch_dir.clone())?;
+/// Receives the files of `manifest` into the staging directory of the upload,
+/// in any order, and returns their staging keys in the order of the manifest.
+///
+/// The content hashes of a `keyed` manifest can only be checked with the key.
+pub(crate) async fn receive_files(
+    state: &State,
+    upload_id: &str,
+    manifest: &[FileLeaf],
+    keyed: bool,
+    multipart: &mut Multipart,
+) -> Result<Vec<String>, Error> {
+    let mut staged: Vec<Option<String>> = vec![None; manifest.len()];
 
     while let Some(field) = multipart.next_field().await? {
+        if field.name() == Some(common::MANIFEST_FIELD) || field.name() == Some(common::TREE_FIELD)
+        {
+            return Err(Error::BadRequest(
+                "only one manifest or tree can be sent".to_string(),
+            ));
+        }
         let filename = if let Some(filename) = field.file_name() {
             filename.to_string()
         } else {
             continue;
         };
 
+        let index = manifest
+            .binary_search_by(|leaf| leaf.path.as_str().cmp(&filename))
+            .map_err(|_| Error::BadRequest(format!("file {} is not in the manifest", filename)))?;
+        if staged[index].is_some() {
+            return Err(Error::BadRequest(format!(
+                "file {} was sent twice",
+                filename
+            )));
+        }
+        let leaf = &manifest[index];
+        // Paths are stored encoded as a single component, but are also restored as is
+        // by clients, possibly in subdirectories.
+        if !FileLeaf::is_safe_path(&leaf.path) {
+            return Err(Error::BadRequest(format!(
+                "invalid file name {:?}",
+                filename
+            )));
+        }
+
         let body_with_io_error = field.map_err(|err| io::Error::new(io::ErrorKind::Other, err));
 
         let body_reader = StreamReader::new(body_with_io_error);
+        let mut hashing_reader = HashingReader::new(body_reader, Context::new(common::DIGEST));
 
-        let real_path = batch_dir.join(filename.clone());
+        let key = format!("{}{}/{}", STAGING_PREFIX, upload_id, encode_name(&filename));
+        let size = state.storage.put(&key, &mut hashing_reader).await?;
+        let content_matches =
+            keyed || digests_match(hashing_reader.finish().as_ref(), &leaf.content_hash);
+        if size != leaf.size || !content_matches {
+            return Err(Error::BadRequest(format!(
+                "content of file {} does not match the manifest",
+                filename
+            )));
+        }
+        staged[index] = Some(key);
+    }
 
-        filename_vec.push(filename.clone());
-        state
-            .client
-            .put_file(batch_dir.clone(), filename.clone(), Box::pin(body_reader))
-            .await?;
-        let mut file = tokio::fs::File::open(real_path.clone()).await?;
-        paths.push(real_path);
+    staged
+        .into_iter()
+        .collect::<Option<_>>()
+        .ok_or(Error::BadRequest(
+            "files of the manifest are missing".to_string(),
+        ))
+}
 
-        let mut bytes = Vec::new();
-        #[allow(clippy::let_underscore_future)]
-        let _ = file.read_to_end(&mut bytes);
-        bytes_vec.push(bytes);
+/// Moves the `staged` files of `leaves` to the batch, and returns their keys.
+/// Keys are unique to the upload, so files are moved before the batch is locked,
+/// and only served once committed.
+pub(crate) async fn move_staged(
+    state: &State,
+    batch_id: &BatchId,
+    upload_id: &str,
+    leaves: &[FileLeaf],
+    staged: &[String],
+) -> Result<Vec<String>, Error> {
+    let mut keys = Vec::with_capacity(staged.len());
+    for (leaf, staged) in leaves.iter().zip(staged) {
+        let key = file_key(batch_id, upload_id, &leaf.path);
+        state.storage.rename(staged, &key).await?;
+        keys.push(key);
     }
+    Ok(keys)
+}
 
-    let tree = MerkleTree::from_vec(common::DIGEST, bytes_vec.clone());
-
+/// Logs the root of `batch`, then saves and serves it.
+/// Returns the batch that was served before.
+///
+/// Must be called while holding `batch_tree_map`, so that the log follows the order
+/// of commits, and with the files of `batch` already in place, so that the lock is
+/// only held to save the log entry and the record of the batch.
+/// The log is saved first, so that a saved batch is always in the log.
+/// If the batch cannot be saved, its entry is removed from the served log.
+pub(crate) async fn commit(
+    state: &State,
+    batch_tree_map: &mut BTreeMap<BatchId, Batch>,
+    batch_id: &BatchId,
+    batch: Batch,
+) -> Result<Option<Batch>, Error> {
+    let mut log = state.log.lock().await;
+    let size = log.size();
+    log.append(
+        batch_id.to_string(),
+        batch.tree.root_hash().clone(),
+        batch.tree.count() as u64,
+    );
     state
-        .batch_tree_map
-        .lock()
+        .catalog
+        .save_log_entry(size, &log.entries()[size])
         .await
-        .insert(batch_id, Batch::new(tree.clone(), paths));
+        .map_err(|err| {
+            log.truncate(size);
+            err
+        })?;
+    if let Err(err) = state.catalog.save_batch(batch_id, &batch).await {
+        log.truncate(size);
+        if let Err(err) = state.catalog.delete_log_entry(size).await {
+            error!(
+                "Entry {} of the log was not removed, because: {:#}",
+                size, err
+            );
+        }
+        return Err(err.into());
+    }
+    Ok(batch_tree_map.insert(batch_id.clone(), batch))
+}
 
-    Ok(())
+/// Receives a batch into the staging directory of the upload, and commits it once
+/// every file is received. Anything left in the staging directory is then removed.
+pub async fn upload(
+    state: Extension<Arc<State>>,
+    batch_id: BatchId,
+    replace: bool,
+    multipart: Multipart,
+) -> Result<(StatusCode, UploadReceipt), Error> {
+    let upload_id = upload_id()?;
+    let result = receive(&state, &batch_id, replace, &upload_id, multipart).await;
+    let committed = matches!(result, Ok((StatusCode::CREATED, _)));
+    clean_up(&state, &batch_id, &upload_id, committed).await;
+    result
+}
+
+async fn receive(
+    state: &State,
+    batch_id: &BatchId,
+    replace: bool,
+    upload_id: &str,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, UploadReceipt), Error> {
+    let tree = receive_manifest(&mut multipart).await?;
+    // An identical batch needs no files, and a conflicting one is refused early.
+    if let Some(receipt) = existing_receipt(
+        &*state.batch_tree_map.lock().await,
+        batch_id,
+        &tree,
+        replace,
+    )? {
+        return Ok((StatusCode::OK, receipt));
+    }
+
+    let manifest: Vec<FileLeaf> = tree.leaves().map(|leaf| leaf.value.clone()).collect();
+    let staged =
+        receive_files(state, upload_id, &manifest, tree.is_keyed(), &mut multipart).await?;
+
+    let keys = move_staged(state, batch_id, upload_id, &manifest, &staged).await?;
+
+    let mut batch_tree_map = state.batch_tree_map.lock().await;
+    // Another upload of the batch may have been committed since the manifest was received.
+    if let Some(receipt) = existing_receipt(&batch_tree_map, batch_id, &tree, replace)? {
+        return Ok((StatusCode::OK, receipt));
+    }
+    let receipt = receipt(batch_id, &tree);
+    let replaced = commit(state, &mut batch_tree_map, batch_id, Batch::new(tree, keys)).await?;
+    drop(batch_tree_map);
+
+    // The files of a replaced batch are only removed once they are no longer served.
+    for key in replaced.into_iter().flat_map(|batch| batch.keys).flatten() {
+        if let Err(err) = state.storage.delete(&key).await {
+            error!("Replaced file {} was not removed, because: {:#}", key, err);
+        }
+    }
+
+    Ok((StatusCode::CREATED, receipt))
 }
 
 pub async fn upload_handler(
     state: Extension<Arc<State>>,
-    Query(UploadParams { batch_id }): Query<UploadParams>,
-    multipart: Multipart,
-) -> Response {
+    params: Result<Query<UploadParams>, QueryRejection>,
+    multipart: Result<Multipart, MultipartRejection>,
+) -> Result<(StatusCode, Json<UploadReceipt>), Error> {
     info!("About to upload a batch");
-    match upload(state, batch_id, multipart).await {
-        Ok(_) => Response::builder()
-            .status(StatusCode::CREATED)
-            .body(boxed("OK".to_string()))
-            .unwrap(),
-        Err(err) => Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body(boxed(err.to_string()))
-            .unwrap(),
-    }
+    let Query(UploadParams { batch_id, replace }) =
+        params.map_err(|err| Error::BadRequest(err.body_text()))?;
+    let multipart = multipart.map_err(|err| Error::BadRequest(err.body_text()))?;
+    let (status, receipt) = upload(state, batch_id, replace, multipart).await?;
+    Ok((status, Json(receipt)))
 }